@@ -0,0 +1,96 @@
+// This module adds things to projects that already exist.
+
+use crate::bundle::{current_plugin_package, select_packages};
+use crate::cargo_toml::find_workspace_root;
+use crate::cli::CiProvider;
+use crate::create::Templates;
+use crate::create::{create_bench, create_lib_crate, create_plugin, create_presets, write_ci};
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use std::env::current_dir;
+use std::path::Path;
+
+/// Adds a shared library crate to the current workspace.
+pub fn add_lib(name: &str) -> Result<()> {
+    let Some(root) = find_workspace_root(&current_dir()?) else {
+        bail!("Library crates can only be added to a workspace. Use `npcli new --workspace` to create one.");
+    };
+    let templates = Templates::new(None)?;
+    create_lib_crate(&root, &templates, name)?;
+    println!(
+        "Created {} in {}...",
+        name,
+        root.join("libs").join(name).display()
+    );
+    Ok(())
+}
+
+/// Adds a benchmark of `process` to the given plugin crates, or to every plugin crate.
+pub fn add_bench(packages: Vec<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let templates = Templates::new(None)?;
+    for name in select_packages(&metadata, packages)? {
+        let package = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == name)
+            .with_context(|| format!("There's no package named {} in the workspace", name))?;
+        let dir = package
+            .manifest_path
+            .parent()
+            .context("The package has no directory")?;
+        create_bench(dir.as_std_path(), &templates, &name)?;
+        println!("Added benches/process.rs to {}...", name);
+    }
+    println!("Run it with `npcli bench`.");
+    Ok(())
+}
+
+/// Adds a CI pipeline to the current workspace (or project).
+pub fn add_ci(provider: CiProvider, force: bool) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let path = write_ci(metadata.workspace_root.as_std_path(), provider, force)
+        .context("Use `--force` to replace the existing pipeline")?;
+    println!("Created {}...", path.display());
+    Ok(())
+}
+
+/// Adds another plugin to a plugin crate. Without `package`, that's the crate in the current
+/// directory, or the only plugin crate in the workspace.
+pub fn add_plugin(name: &str, package: Option<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let package = current_plugin_package(&metadata, package)?;
+    let dir: &Path = package
+        .manifest_path
+        .parent()
+        .context("The package has no directory")?
+        .as_std_path();
+
+    let templates = Templates::new(None)?;
+    let module_path = create_plugin(dir, &templates, name)?;
+    println!("Created {}...", module_path.display());
+    println!(
+        "{} now exports {} too. Its IDs were added to npcli.toml...",
+        package.name, name
+    );
+    Ok(())
+}
+
+/// Adds factory presets to a plugin crate, picked like `add_plugin` does.
+pub fn add_presets(package: Option<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let package = current_plugin_package(&metadata, package)?;
+    let dir: &Path = package
+        .manifest_path
+        .parent()
+        .context("The package has no directory")?
+        .as_std_path();
+
+    let templates = Templates::new(None)?;
+    let module_path = create_presets(dir, &templates, &package.version.to_string())?;
+    println!("Created {}...", module_path.display());
+    println!(
+        "Load a preset from the editor with `presets::load()`, and add more with `npcli preset export --from-state`."
+    );
+    Ok(())
+}
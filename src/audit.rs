@@ -0,0 +1,202 @@
+// This module checks the licenses of everything a plugin crate ships with.
+
+use crate::bundle::select_packages;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{DependencyKind, Metadata, MetadataCommand, Package, PackageId};
+use cliclack::log::{info, remark, success, warning};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+
+pub const NOTICES_FILENAME: &str = "THIRD_PARTY_NOTICES";
+
+/// License identifiers that require derived works to use the same license.
+const COPYLEFT: &[&str] = &[
+    "GPL-",
+    "AGPL-",
+    "LGPL-",
+    "MPL-",
+    "EPL-",
+    "EUPL-",
+    "CDDL-",
+    "OSL-",
+    "CC-BY-SA-",
+];
+
+/// Lists the licenses of every crate that the given packages (or every plugin crate) ship with,
+/// and writes a THIRD_PARTY_NOTICES file next to each package's Cargo.toml, for `npcli package`.
+/// Fails if a package with a permissive license depends on a copyleft crate.
+pub fn audit_licenses(packages: Vec<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().exec()?;
+    let mut problems = Vec::new();
+
+    for name in select_packages(&metadata, packages)? {
+        let package = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == name)
+            .with_context(|| format!("There's no package named {} in the workspace", name))?;
+        let license = package.license.as_deref().unwrap_or("Proprietary");
+        let permissive = !is_copyleft(license);
+        info(format!("{} ({})", name, license))?;
+
+        let dependencies = shipped_dependencies(&metadata, package)?;
+        let mut by_license: BTreeMap<&str, Vec<&Package>> = BTreeMap::new();
+        for dependency in &dependencies {
+            let license = dependency.license.as_deref().unwrap_or("unknown");
+            by_license.entry(license).or_default().push(dependency);
+        }
+
+        for (license, crates) in &by_license {
+            let crates: Vec<String> = crates
+                .iter()
+                .map(|package| format!("{} {}", package.name, package.version))
+                .collect();
+            let line = format!("{}: {}", license, crates.join(", "));
+            if *license == "unknown" {
+                warning(line)?;
+                remark("These crates don't declare a license in their Cargo.toml. Check their license files by hand.")?;
+            } else if permissive && is_copyleft(license) {
+                warning(&line)?;
+                problems.push(format!("{} depends on {}", name, line));
+            } else {
+                remark(line)?;
+            }
+        }
+
+        let notices_path = package
+            .manifest_path
+            .parent()
+            .context("The package has no directory")?
+            .join(NOTICES_FILENAME);
+        write_notices(notices_path.as_std_path(), &name, &dependencies)?;
+        success(format!(
+            "{} crates, written to {}",
+            dependencies.len(),
+            notices_path
+        ))?;
+    }
+
+    if !problems.is_empty() {
+        bail!(
+            "Copyleft dependencies were found in crates with a permissive license:\n{}\nEither change the license, or replace those dependencies. Note that VST3 exports always need GPL-3.0.",
+            problems.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Whether every alternative of an SPDX expression (e.g. `MIT OR Apache-2.0`) is copyleft,
+/// i.e. there's no way to use the crate under a permissive license.
+fn is_copyleft(expression: &str) -> bool {
+    let expression = expression.replace(['(', ')'], " ");
+    // old crates still use `/` instead of `OR`
+    expression
+        .split(" OR ")
+        .flat_map(|alternative| alternative.split('/'))
+        .all(|alternative| {
+            alternative
+                .split(" AND ")
+                .map(str::trim)
+                .any(|license| COPYLEFT.iter().any(|prefix| license.starts_with(prefix)))
+        })
+}
+
+/// Every crate that ends up in the package's binaries, i.e. its normal dependencies, recursively.
+/// Build and dev dependencies aren't shipped, so they're skipped.
+fn shipped_dependencies<'a>(metadata: &'a Metadata, package: &Package) -> Result<Vec<&'a Package>> {
+    let resolve = metadata
+        .resolve
+        .as_ref()
+        .context("cargo metadata didn't resolve the dependencies")?;
+    let workspace: HashSet<&PackageId> = metadata.workspace_members.iter().collect();
+
+    let mut seen: HashSet<&PackageId> = HashSet::new();
+    let mut queue = vec![&package.id];
+    while let Some(id) = queue.pop() {
+        let Some(node) = resolve.nodes.iter().find(|node| &node.id == id) else {
+            continue;
+        };
+        for dependency in &node.deps {
+            let shipped = dependency
+                .dep_kinds
+                .iter()
+                .any(|info| info.kind == DependencyKind::Normal);
+            if shipped && seen.insert(&dependency.pkg) {
+                queue.push(&dependency.pkg);
+            }
+        }
+    }
+
+    // the workspace's own crates (e.g. a shared DSP library) fall under the project's license
+    let mut packages: Vec<&Package> = seen
+        .into_iter()
+        .filter(|id| !workspace.contains(id))
+        .map(|id| &metadata[id])
+        .collect();
+    packages.sort_by(|a, b| a.name.cmp(&b.name).then(a.version.cmp(&b.version)));
+    Ok(packages)
+}
+
+/// Writes every dependency's name, license, and license texts.
+fn write_notices(path: &Path, name: &str, dependencies: &[&Package]) -> Result<()> {
+    let separator = "=".repeat(80);
+    let mut notices = format!(
+        "THIRD-PARTY SOFTWARE NOTICES\n\n{} includes the following third-party crates.\n",
+        name
+    );
+    for dependency in dependencies {
+        notices.push_str(&format!(
+            "\n{}\n{} {} ({})\n",
+            separator,
+            dependency.name,
+            dependency.version,
+            dependency.license.as_deref().unwrap_or("unknown license")
+        ));
+        if let Some(repository) = &dependency.repository {
+            notices.push_str(&format!("{}\n", repository));
+        }
+        for file in license_files(dependency) {
+            if let Ok(text) = read_to_string(&file) {
+                notices.push_str(&format!("\n{}\n", text.trim_end()));
+            }
+        }
+    }
+    write(path, notices)?;
+    Ok(())
+}
+
+/// The crate's `license-file`, plus anything like `LICENSE-MIT`, `COPYING` or `NOTICE` next to its Cargo.toml.
+fn license_files(package: &Package) -> Vec<PathBuf> {
+    let Some(dir) = package.manifest_path.parent() else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = dir
+        .as_std_path()
+        .read_dir()
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.is_file())
+                .filter(|path| {
+                    let name = path
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_ascii_uppercase())
+                        .unwrap_or_default();
+                    ["LICENSE", "LICENCE", "COPYING", "NOTICE"]
+                        .iter()
+                        .any(|prefix| name.starts_with(prefix))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(license_file) = package.license_file() {
+        let license_file = license_file.into_std_path_buf();
+        if !files.contains(&license_file) {
+            files.push(license_file);
+        }
+    }
+    files.sort();
+    files
+}
@@ -0,0 +1,123 @@
+// This module runs the benchmarks from `npcli add bench`, optionally against another git ref.
+
+use crate::bundle::select_packages;
+use crate::git::git;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use cliclack::log::{info, step};
+use std::env::set_current_dir;
+use std::path::Path;
+use std::process::Command;
+
+/// Runs the `process` benchmark of the given packages (or every plugin crate).
+///
+/// With `compare` set, that git ref is checked out into a temporary worktree and measured first.
+/// Both runs share the workspace's target directory, so criterion saves the ref's results as a
+/// baseline there and compares the working tree against it.
+pub fn bench(
+    packages: Vec<String>,
+    compare: Option<String>,
+    other_args: Vec<String>,
+) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let workspace_root = metadata.workspace_root.as_std_path();
+    set_current_dir(workspace_root)?;
+    let packages = select_packages(&metadata, packages)?;
+    let target_dir = metadata.target_directory.as_std_path();
+
+    let Some(git_ref) = compare else {
+        return cargo_bench(workspace_root, target_dir, &packages, &other_args, &[]);
+    };
+
+    // criterion uses the baseline's name as a directory name
+    let baseline: String = git_ref
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let worktree = target_dir.join("npcli-bench").join(&baseline);
+    let worktree_str = worktree.to_string_lossy().into_owned();
+    if worktree.exists() {
+        git(
+            workspace_root,
+            &["worktree", "remove", "--force", &worktree_str],
+        )?;
+    }
+
+    // the workspace may not be at the root of the repository
+    let repo_root = git(workspace_root, &["rev-parse", "--show-toplevel"])?;
+    let workspace_in_repo = workspace_root
+        .strip_prefix(repo_root.trim())
+        .unwrap_or(Path::new(""));
+
+    step(format!("Measuring {}...", git_ref))?;
+    git(
+        workspace_root,
+        &["worktree", "add", "--detach", &worktree_str, &git_ref],
+    )?;
+    let result = cargo_bench(
+        &worktree.join(workspace_in_repo),
+        target_dir,
+        &packages,
+        &other_args,
+        &["--save-baseline", &baseline],
+    );
+    git(
+        workspace_root,
+        &["worktree", "remove", "--force", &worktree_str],
+    )?;
+    result.with_context(|| {
+        format!(
+            "Failed to benchmark {}. Does it have the benchmark yet?",
+            git_ref
+        )
+    })?;
+
+    step("Measuring the working tree...")?;
+    cargo_bench(
+        workspace_root,
+        target_dir,
+        &packages,
+        &other_args,
+        &["--baseline", &baseline],
+    )?;
+    info(format!(
+        "The changes above are relative to {}. Criterion's report is in \"{}\".",
+        git_ref,
+        target_dir.join("criterion").join("report").display()
+    ))?;
+    Ok(())
+}
+
+/// Runs `cargo bench` in `dir`, passing `criterion_args` on to criterion.
+fn cargo_bench(
+    dir: &Path,
+    target_dir: &Path,
+    packages: &[String],
+    args: &[String],
+    criterion_args: &[&str],
+) -> Result<()> {
+    let mut command = Command::new("cargo");
+    command
+        .current_dir(dir)
+        .env("CARGO_TARGET_DIR", target_dir)
+        .args(["bench", "--bench", "process"]);
+    for package in packages {
+        command.arg("-p").arg(package);
+    }
+    let status = command
+        .args(args)
+        .arg("--")
+        .args(criterion_args)
+        .status()
+        .context("Failed to run cargo")?;
+    if !status.success() {
+        bail!("cargo bench failed. Run `npcli add bench` first if there's no benchmark yet.");
+    }
+    Ok(())
+}
@@ -0,0 +1,91 @@
+// This module compiles and bundles existing plugin crates, including workspace members.
+
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand, Package};
+use nih_plug_xtask::{build, bundle};
+use std::env::{current_dir, set_current_dir};
+use std::path::Path;
+
+/// Builds and bundles the given packages from the workspace root.
+/// If no packages are given, every plugin crate in the workspace is bundled.
+pub fn bundle_packages(packages: Vec<String>, other_args: Vec<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    // bundler.toml (if any) lives in the workspace root, so that's where we have to be
+    set_current_dir(&metadata.workspace_root)?;
+
+    let packages = select_packages(&metadata, packages)?;
+    build_and_bundle(
+        metadata.target_directory.as_std_path(),
+        &packages,
+        &other_args,
+    )
+}
+
+/// Builds the packages with cargo, then bundles them.
+pub fn build_and_bundle(target_dir: &Path, packages: &[String], args: &[String]) -> Result<()> {
+    build(packages, args)?;
+    bundle_built(target_dir, packages, args)
+}
+
+/// Bundles packages that have already been built with the same `args`.
+pub fn bundle_built(target_dir: &Path, packages: &[String], args: &[String]) -> Result<()> {
+    for package in packages {
+        bundle(target_dir, package, args, false)?;
+    }
+    Ok(())
+}
+
+/// Returns the given packages, or every plugin crate in the workspace if none are given.
+pub fn select_packages(metadata: &Metadata, packages: Vec<String>) -> Result<Vec<String>> {
+    if !packages.is_empty() {
+        return Ok(packages);
+    }
+    let packages: Vec<String> = plugin_packages(metadata)
+        .into_iter()
+        .map(|package| package.name.clone())
+        .collect();
+    if packages.is_empty() {
+        bail!("No plugin crates were found. A plugin crate must have the `cdylib` crate type.");
+    }
+    Ok(packages)
+}
+
+/// Returns every workspace member that builds a `cdylib`, i.e. every plugin.
+pub fn plugin_packages(metadata: &Metadata) -> Vec<&Package> {
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .filter(|package| {
+            package
+                .targets
+                .iter()
+                .any(|target| target.crate_types.iter().any(|kind| kind == "cdylib"))
+        })
+        .collect()
+}
+
+/// Returns the plugin crate named `package`. Without one, that's the crate in the current
+/// directory, or the only plugin crate in the workspace.
+pub fn current_plugin_package(metadata: &Metadata, package: Option<String>) -> Result<&Package> {
+    let plugins = plugin_packages(metadata);
+    if let Some(package) = package {
+        return plugins
+            .into_iter()
+            .find(|plugin| plugin.name == package)
+            .with_context(|| {
+                format!("There's no plugin crate named {} in the workspace", package)
+            });
+    }
+    let current_dir = current_dir()?;
+    let in_current_dir = plugins.iter().copied().find(|plugin| {
+        plugin
+            .manifest_path
+            .parent()
+            .is_some_and(|dir| current_dir.starts_with(dir))
+    });
+    match (in_current_dir, plugins.as_slice()) {
+        (Some(plugin), _) => Ok(plugin),
+        (None, [plugin]) => Ok(plugin),
+        _ => bail!("There's more than one plugin crate here. Pick one with `--package`."),
+    }
+}
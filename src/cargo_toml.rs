@@ -0,0 +1,103 @@
+// This module edits Cargo.toml files without losing the user's formatting and comments.
+
+use anyhow::{Context, Result};
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+use toml_edit::{value, Array, ArrayOfTables, DocumentMut, Item, Table};
+
+/// A Cargo.toml that has been read into memory. Nothing is written until `save()` is called.
+pub struct CargoToml {
+    path: PathBuf,
+    pub doc: DocumentMut,
+}
+
+impl CargoToml {
+    /// Reads the Cargo.toml in the given directory.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let path = dir.as_ref().join("Cargo.toml");
+        let contents = read_to_string(&path)
+            .with_context(|| format!("Failed to read \"{}\"", path.display()))?;
+        let doc = contents
+            .parse::<DocumentMut>()
+            .with_context(|| format!("Failed to parse \"{}\"", path.display()))?;
+        Ok(Self { path, doc })
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write(&self.path, self.doc.to_string())?;
+        Ok(())
+    }
+
+    /// Returns the table at the given dotted key, e.g. `workspace.dependencies`,
+    /// creating any tables along the way.
+    pub fn table_mut(&mut self, key: &str) -> Result<&mut Table> {
+        let mut table = self.doc.as_table_mut();
+        for part in key.split('.') {
+            let item = table.entry(part).or_insert_with(|| {
+                let mut new_table = Table::new();
+                // don't print empty parent tables like `[workspace]`
+                new_table.set_implicit(true);
+                Item::Table(new_table)
+            });
+            table = item
+                .as_table_mut()
+                .with_context(|| format!("`{}` in Cargo.toml is not a table", key))?;
+        }
+        Ok(table)
+    }
+
+    /// Whether this manifest is a workspace root.
+    pub fn is_workspace(&self) -> bool {
+        self.doc.contains_table("workspace")
+    }
+
+    /// Adds `member` to `workspace.members`, unless it's already listed.
+    pub fn add_workspace_member(&mut self, member: &str) -> Result<()> {
+        let workspace = self.table_mut("workspace")?;
+        let members = workspace
+            .entry("members")
+            .or_insert_with(|| Item::Value(Array::new().into()))
+            .as_array_mut()
+            .context("`workspace.members` in Cargo.toml is not an array")?;
+
+        if !members
+            .iter()
+            .any(|existing| existing.as_str() == Some(member))
+        {
+            members.push(member);
+        }
+        Ok(())
+    }
+
+    /// Adds a `[[bench]]` target without the default test harness (as criterion needs),
+    /// unless there's already a benchmark with that name.
+    pub fn add_bench(&mut self, name: &str) -> Result<()> {
+        let benches = self
+            .doc
+            .as_table_mut()
+            .entry("bench")
+            .or_insert_with(|| Item::ArrayOfTables(ArrayOfTables::new()))
+            .as_array_of_tables_mut()
+            .context("`bench` in Cargo.toml is not an array of tables")?;
+
+        if !benches
+            .iter()
+            .any(|bench| bench.get("name").and_then(Item::as_str) == Some(name))
+        {
+            let mut bench = Table::new();
+            bench.insert("name", value(name));
+            bench.insert("harness", value(false));
+            benches.push(bench);
+        }
+        Ok(())
+    }
+}
+
+/// Walks up from `start` and returns the first directory whose Cargo.toml is a workspace root.
+pub fn find_workspace_root(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .filter(|dir| dir.join("Cargo.toml").is_file())
+        .find(|dir| CargoToml::open(dir).is_ok_and(|manifest| manifest.is_workspace()))
+        .map(Path::to_path_buf)
+}
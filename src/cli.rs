@@ -1,4 +1,5 @@
-use clap::{command, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 /// NIH-Plug CLI
 #[derive(Parser, Debug)]
@@ -10,22 +11,10 @@ pub struct Cli {
 #[derive(Subcommand, Debug)]
 pub enum Commands {
     /// Create a new NIH-Plug project.
-    New {
-        /// Enabling this will skip all user input and simply create a project with defaults.
-        #[arg(short, long, requires = "name")]
-        defaults: bool,
-
-        /// Optionally, provide the project (NOT plugin) name here. If you enabled the defaults flag, you MUST supply something here.
-        #[arg(long)]
-        name: Option<String>,
-
-        /// Use this flag if you want to skip initial compilation.
-        #[arg(short, long)]
-        skip_build: bool,
-    },
+    New(NewArgs),
     /// Compile an existing NIH-Plug project
     Bundle {
-        /// Package(s) to compile.
+        /// Package(s) to compile. If none are given, every plugin crate in the workspace is compiled.
         packages: Vec<String>,
 
         /// Any other arguments supported by cargo, such as profile arguments (`--release`), may be supplied here.
@@ -37,4 +26,313 @@ pub enum Commands {
     BundleUniversal {
         packages: String,
     },
+    /// Install bundled plugins into the plugin folders that hosts scan (e.g. `~/.vst3` and `~/.clap`).
+    /// The folders can be overridden with `VST3_PATH` and `CLAP_PATH`.
+    Install {
+        /// Package(s) to install. If none are given, every plugin crate in the workspace is installed.
+        packages: Vec<String>,
+
+        /// Link to the bundles in `target/bundled` instead of copying them, so rebuilding updates the installed plugins.
+        #[arg(long)]
+        symlink: bool,
+
+        /// Install for all users instead of just the current user. This usually requires admin rights.
+        #[arg(long)]
+        system: bool,
+    },
+    /// Remove plugins that were installed with `npcli install`.
+    Uninstall {
+        /// Package(s) to uninstall. If none are given, every plugin crate in the workspace is uninstalled.
+        packages: Vec<String>,
+
+        /// Uninstall from the system-wide plugin folders instead.
+        #[arg(long)]
+        system: bool,
+    },
+    /// Rebuild, bundle, and install (as symlinks) plugins whenever their sources change.
+    Dev {
+        /// Package(s) to watch. If none are given, every plugin crate in the workspace is watched.
+        packages: Vec<String>,
+
+        /// Any other arguments supported by cargo, e.g. `-- --release`.
+        #[arg(raw = true)]
+        other_args: Vec<String>,
+    },
+    /// Check a bundled `.clap` or `.vst3` plugin without loading it in a host.
+    /// The IDs are checked against the plugin crate's `npcli.toml`.
+    Validate {
+        /// The bundle(s) to check, e.g. `target/bundled/gain.vst3`.
+        #[arg(required = true)]
+        bundles: Vec<PathBuf>,
+
+        /// Also run clap-validator (for CLAP) or pluginval (for VST3) if they're installed.
+        #[arg(long)]
+        external: bool,
+    },
+    /// Run a WAV file through a bundled CLAP plugin and write the result, without a DAW or sound card.
+    Render {
+        /// The WAV file to process.
+        #[arg(long, short)]
+        input: PathBuf,
+
+        /// Where to write the processed audio (as 32-bit float WAV).
+        #[arg(long, short)]
+        output: PathBuf,
+
+        /// Set a parameter by name before processing, e.g. `--param gain=-6dB`. Can be repeated.
+        #[arg(long = "param", value_name = "NAME=VALUE")]
+        params: Vec<String>,
+
+        /// The `.clap` bundle to load. Defaults to the bundled CLAP plugin of the current project.
+        #[arg(long, conflicts_with = "package")]
+        plugin: Option<PathBuf>,
+
+        /// The plugin crate to render with, if the workspace has more than one.
+        #[arg(long, short)]
+        package: Option<String>,
+
+        /// The number of frames processed at once.
+        #[arg(long, default_value_t = 512, value_parser = clap::value_parser!(u32).range(1..))]
+        block_size: u32,
+    },
+    /// Run the benchmarks created by `npcli add bench`.
+    Bench {
+        /// Package(s) to benchmark. If none are given, every plugin crate in the workspace is benchmarked.
+        packages: Vec<String>,
+
+        /// Measure this git ref (e.g. a branch) first, and compare the working tree against it.
+        #[arg(long, value_name = "REF")]
+        compare: Option<String>,
+
+        /// Any other arguments supported by cargo, e.g. `-- --features ...`.
+        #[arg(raw = true)]
+        other_args: Vec<String>,
+    },
+    /// Package bundled plugins into release archives with SHA256 checksums. Run `npcli bundle -- --release` first.
+    Package {
+        /// Package(s) to archive. If none are given, every plugin crate in the workspace is archived.
+        packages: Vec<String>,
+
+        /// Where to write the archives. Defaults to `target/dist`.
+        #[arg(long, value_name = "DIR")]
+        out_dir: Option<PathBuf>,
+
+        /// Also build a `.deb` of the standalone binary (Linux only).
+        #[arg(long)]
+        deb: bool,
+
+        /// Also build an AppImage of the standalone binary, using appimagetool (Linux only).
+        #[arg(long)]
+        appimage: bool,
+    },
+    /// Show what's in plugin crates: their plugins' names, IDs, formats, MIDI and audio layouts, and parameters.
+    Info {
+        /// Package(s) to inspect. If none are given, every plugin crate in the workspace is inspected.
+        packages: Vec<String>,
+
+        /// Print the details as JSON instead.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare the params of plugin crates against an earlier git revision, and fail on changes that break saved sessions.
+    CheckParams {
+        /// Package(s) to check. If none are given, every plugin crate in the workspace is checked.
+        packages: Vec<String>,
+
+        /// The git ref to compare against, e.g. the last release's tag.
+        #[arg(long, value_name = "REF")]
+        against: String,
+    },
+    /// Keep track of the CLAP and VST3 IDs of every plugin npcli has created or inspected, to catch reused IDs.
+    Ids {
+        #[command(subcommand)]
+        command: IdsCommands,
+    },
+    /// Work with the factory presets that `npcli add presets` set up.
+    Preset {
+        #[command(subcommand)]
+        command: PresetCommands,
+    },
+    /// List the licenses of every crate a plugin ships with, flag copyleft ones, and write THIRD_PARTY_NOTICES.
+    AuditLicenses {
+        /// Package(s) to audit. If none are given, every plugin crate in the workspace is audited.
+        packages: Vec<String>,
+    },
+    /// Manage the versions of plugin crates.
+    Version {
+        #[command(subcommand)]
+        command: VersionCommands,
+    },
+    /// Check that everything needed to build NIH-Plug plugins is installed.
+    Doctor,
+    /// Add something to an existing NIH-Plug project or workspace.
+    Add {
+        #[command(subcommand)]
+        command: AddCommands,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AddCommands {
+    /// Add a plain library crate to the workspace, e.g. for DSP code shared between plugins.
+    /// Every plugin crate in the workspace gets it as a dependency.
+    Lib {
+        /// The name of the library crate, which is created in `libs/<name>`.
+        name: String,
+    },
+    /// Add a criterion benchmark that runs `process` at several buffer sizes and sample rates.
+    /// The plugin has to export CLAP, since the benchmark loads it through a tiny CLAP host.
+    Bench {
+        /// Package(s) to add the benchmark to. If none are given, every plugin crate gets one.
+        packages: Vec<String>,
+    },
+    /// Add another plugin to an existing plugin crate, in its own module.
+    Plugin {
+        /// The new plugin's name, which is also its type name.
+        name: String,
+
+        /// The crate to add it to. Defaults to the crate in the current directory.
+        #[arg(long, short)]
+        package: Option<String>,
+    },
+    /// Add a factory preset module, with presets in `presets/` that are compiled into the plugin.
+    /// The params struct gets a persisted field that remembers the last loaded preset.
+    Presets {
+        /// The crate to add them to. Defaults to the crate in the current directory.
+        #[arg(long, short)]
+        package: Option<String>,
+    },
+    /// Add a CI pipeline that tests the workspace and bundles every plugin crate in it.
+    Ci {
+        /// Where the pipeline runs.
+        #[arg(value_enum, default_value = "github")]
+        provider: CiProvider,
+
+        /// Replace the existing pipeline, e.g. after adding a plugin crate.
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PresetCommands {
+    /// Turn a saved plugin state (JSON) into a factory preset, and add it to `src/presets.rs`.
+    Export {
+        /// The state to export, e.g. one saved by the plugin's tests.
+        #[arg(long)]
+        from_state: PathBuf,
+
+        /// The preset's name. Defaults to the state file's name.
+        #[arg(long)]
+        name: Option<String>,
+
+        /// The crate to add it to. Defaults to the crate in the current directory.
+        #[arg(long, short)]
+        package: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum IdsCommands {
+    /// Scan projects for CLAP and VST3 IDs that another plugin also uses, and add them to the registry.
+    Check {
+        /// Projects, or directories of projects, to scan. Defaults to the current directory.
+        dirs: Vec<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VersionCommands {
+    /// Bump the version in Cargo.toml, add a changelog section, then commit and tag the release.
+    /// The working tree has to be clean.
+    Bump {
+        /// Which part of the version to bump. A pre-release that's already at that level, like
+        /// 1.2.0-beta.1 for `minor`, is released instead.
+        level: BumpLevel,
+
+        /// Package(s) to bump. If none are given, every plugin crate in the workspace is bumped.
+        packages: Vec<String>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum BumpLevel {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Args, Debug)]
+pub struct NewArgs {
+    /// Enabling this will skip all user input and simply create a project with defaults.
+    #[arg(short, long, requires = "name")]
+    pub defaults: bool,
+
+    /// Optionally, provide the project (NOT plugin) name here. If you enabled the defaults flag, you MUST supply something here.
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Use this flag if you want to skip initial compilation.
+    #[arg(short, long)]
+    pub skip_build: bool,
+
+    /// A directory of templates (`lib.txt`, `clap.txt`, `vst3.txt`, `main.txt`) to use instead of the built-in ones.
+    /// Directories listed in `NPCLI_TEMPLATE_PATH` are searched next, and any template not found falls back to the built-in one.
+    #[arg(long)]
+    pub template_dir: Option<PathBuf>,
+
+    /// Create the project from a template repository instead, e.g. `gh:org/repo[@ref][/subdir]`.
+    /// The template's `npcli-template.toml` decides which prompts are asked.
+    #[arg(long, conflicts_with = "template_dir")]
+    pub template: Option<String>,
+
+    /// Use the cached copy of `--template` instead of fetching it.
+    #[arg(long, requires = "template")]
+    pub offline: bool,
+
+    /// Create a new workspace in this directory, with the project as its first member in `plugins/`.
+    /// Running `npcli new` inside an existing workspace adds a member to it, so this isn't needed then.
+    #[arg(long, value_name = "DIR")]
+    pub workspace: Option<PathBuf>,
+
+    /// Also generate a CI pipeline that tests the project and bundles it on Linux, Windows and macOS.
+    #[arg(long, value_enum, value_name = "PROVIDER")]
+    pub ci: Option<CiProvider>,
+
+    /// Generate `params.rs`, `dsp.rs`, `editor.rs` and `formats.rs` (the CLAP/VST3 impls) instead of one big `lib.rs`.
+    #[arg(long, conflicts_with = "template")]
+    pub split: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CiProvider {
+    Github,
+    Gitlab,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(block_size: &str) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from([
+            "npcli",
+            "render",
+            "--input",
+            "in.wav",
+            "--output",
+            "out.wav",
+            "--block-size",
+            block_size,
+        ])
+    }
+
+    #[test]
+    fn rejects_empty_blocks() {
+        assert!(render("0").is_err());
+        let Commands::Render { block_size, .. } = render("1").unwrap().command else {
+            panic!("expected the render command");
+        };
+        assert_eq!(block_size, 1);
+    }
 }
@@ -1,11 +1,8 @@
 // This module contains boilerplate structs,
 
-use super::config::{
-    DEFAULT_EMAIL, DEFAULT_MIDI_CONFIG, DEFAULT_NAME, DEFAULT_SUB_CATEGORY, DEFAULT_URL,
-    DEFAULT_VENDOR, DEFAULT_VST_ID,
-};
+use serde::Serialize;
 
-#[derive(boilerplate::Boilerplate)]
+#[derive(boilerplate::Boilerplate, Serialize)]
 #[boilerplate(filename = "lib.txt")]
 #[derive(Clone)]
 pub struct LibConfig {
@@ -13,29 +10,19 @@ pub struct LibConfig {
     pub vendor: String,
     pub url: String,
     pub email: String,
+    pub license: String,
     pub midi_config: String,
+    pub midi_output: String,
 }
 
-impl Default for LibConfig {
-    fn default() -> Self {
-        Self {
-            plugin_name: DEFAULT_NAME.to_string(),
-            vendor: DEFAULT_VENDOR.to_string(),
-            url: DEFAULT_URL.to_string(),
-            email: DEFAULT_EMAIL.to_string(),
-            midi_config: DEFAULT_MIDI_CONFIG.to_string(),
-        }
-    }
-}
-
-#[derive(boilerplate::Boilerplate)]
+#[derive(boilerplate::Boilerplate, Serialize)]
 #[boilerplate(filename = "main.txt")]
 pub struct StandaloneConfig {
     pub plugin_name: String,
     pub project_name: String,
 }
 
-#[derive(boilerplate::Boilerplate)]
+#[derive(boilerplate::Boilerplate, Serialize)]
 #[boilerplate(filename = "clap.txt")]
 pub struct ClapConfig {
     pub plugin_name: String,
@@ -44,7 +31,39 @@ pub struct ClapConfig {
     pub clap_features: String,
 }
 
-#[derive(boilerplate::Boilerplate)]
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "dsp.txt")]
+pub struct DspConfig {
+    pub lib_name: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "tests.txt")]
+pub struct TestsConfig {
+    pub crate_name: String,
+    pub plugin_name: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "clap_tests.txt")]
+pub struct ClapTestsConfig {
+    pub crate_name: String,
+    pub clap_entry: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "bench.txt")]
+pub struct BenchConfig {
+    pub crate_name: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "presets.txt")]
+pub struct PresetsConfig {
+    pub plugin_name: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
 #[boilerplate(filename = "vst3.txt")]
 pub struct Vst3Config {
     pub plugin_name: String,
@@ -52,12 +71,33 @@ pub struct Vst3Config {
     pub sub_categories: String,
 }
 
-impl Default for Vst3Config {
-    fn default() -> Self {
-        Self {
-            plugin_name: DEFAULT_NAME.to_string(),
-            vst_id: DEFAULT_VST_ID.to_string(),
-            sub_categories: DEFAULT_SUB_CATEGORY.to_string(),
-        }
-    }
+// `--split` generates these modules instead of one lib.rs
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "split_lib.txt")]
+pub struct SplitLibConfig {
+    pub plugin_name: String,
+    pub vendor: String,
+    pub url: String,
+    pub email: String,
+    pub midi_config: String,
+    pub midi_output: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "split_params.txt")]
+pub struct SplitParamsConfig {
+    pub plugin_name: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "split_dsp.txt")]
+pub struct SplitDspConfig {
+    pub plugin_name: String,
+}
+
+#[derive(boilerplate::Boilerplate, Serialize)]
+#[boilerplate(filename = "split_editor.txt")]
+pub struct SplitEditorConfig {
+    pub plugin_name: String,
 }
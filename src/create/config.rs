@@ -1,68 +1,58 @@
 use super::{
     boilerplate::{ClapConfig, LibConfig, Vst3Config},
+    manifest::{Answer, Answers, Prompt, PromptKind, TemplateManifest},
     ExportType,
 };
-use anyhow::Result;
-use cliclack::{input, intro, multiselect, select};
-pub const DEFAULT_NAME: &str = "Gain";
-pub const DEFAULT_VENDOR: &str = "NIH-Plug";
-pub const DEFAULT_URL: &str = "https://github.com/robbert-vdh/nih-plug";
-pub const DEFAULT_EMAIL: &str = "info@example.com";
-pub const DEFAULT_VST_ID: &str = "Exactly16Chars!!";
-pub const DEFAULT_MIDI_CONFIG: &str = "None";
-pub const DEFAULT_SUB_CATEGORY: &str = "Vst3SubCategory::Fx";
-
-const DEFAULT_CLAP_ID: &str = "com.moist-plugins-gmbh.gain";
-const DEFAULT_CLAP_DESCRIPTION: &str = "A smoothed gain parameter example plugin";
+use anyhow::{bail, Result};
+use cliclack::{confirm, input, multiselect, select};
+// the built-in prompts are data too, just like a template's prompts
+const LIB_PROMPTS: &str = include_str!("../../templates/prompts/lib.toml");
+const VST3_PROMPTS: &str = include_str!("../../templates/prompts/vst3.toml");
+const CLAP_PROMPTS: &str = include_str!("../../templates/prompts/clap.toml");
+
+/// Asks the built-in prompts in `manifest`. With `defaults` set, nothing is asked.
+fn ask_builtin(manifest: &str, defaults: bool) -> Result<Answers> {
+    let manifest = TemplateManifest::parse(manifest)?;
+    ask_prompts(&manifest, defaults)
+}
 
 // TODO: choose a better name LMAO
-pub fn configure_lib() -> Result<LibConfig> {
+pub fn configure_lib(defaults: bool) -> Result<LibConfig> {
     // get user input for basic plugin info
-    let plugin_name: String = input("What's your plugin named?")
-        .placeholder(DEFAULT_NAME)
-        .default_input(DEFAULT_NAME)
-        .interact()?;
-
-    let vendor: String = input("Author?")
-        .placeholder(DEFAULT_VENDOR)
-        .default_input(DEFAULT_VENDOR)
-        .interact()?;
-
-    let url: String = input("URL?")
-        .placeholder(DEFAULT_URL)
-        .default_input(DEFAULT_URL)
-        .interact()?;
-
-    let email: String = input("Email?")
-        .placeholder(DEFAULT_EMAIL)
-        .default_input(DEFAULT_EMAIL)
-        .interact()?;
-
-    /*
-     *
-     * NOTE:
-     * Audio config is not included here,
-     * because some DAWs (Ableton, for example) do NOT support plugins with "weird" audio configs (0 outputs, etc.)
-     *
-     */
+    let answers = ask_builtin(LIB_PROMPTS, defaults)?;
+    Ok(LibConfig {
+        plugin_name: answers.get("plugin_name")?,
+        vendor: answers.get("vendor")?,
+        url: answers.get("url")?,
+        email: answers.get("email")?,
+        license: answers.get("license")?,
+        midi_config: answers.get("midi_config")?,
+        midi_output: answers.get("midi_output")?,
+    })
+}
 
-    let midi_config: String = select("MIDI Config?")
-    .item("None", "None", "The plugin will not receive MIDI events.")
-    .item("Basic", "Basic", "The plugin receives note on/off/choke events, pressure, and possibly standardized expression types.")
-    .item(
-        "MidiCCs",
-        "Full",
-        "The plugin receives full MIDI CCs as well as pitch bend information.",
-    )
-    .initial_value("None")
-    .interact()?
-    .to_owned();
+/// Like `configure_lib`, but for another plugin in an existing crate.
+/// The vendor info is shared with the crate's other plugins, so only the plugin's own settings are asked.
+pub fn configure_extra_plugin(
+    plugin_name: &str,
+    vendor: String,
+    url: String,
+    email: String,
+    license: String,
+) -> Result<LibConfig> {
+    let mut manifest = TemplateManifest::parse(LIB_PROMPTS)?;
+    manifest
+        .prompts
+        .retain(|prompt| prompt.name.starts_with("midi_"));
+    let answers = ask_prompts(&manifest, false)?;
     Ok(LibConfig {
-        plugin_name,
+        plugin_name: plugin_name.to_string(),
         vendor,
         url,
         email,
-        midi_config,
+        license,
+        midi_config: answers.get("midi_config")?,
+        midi_output: answers.get("midi_output")?,
     })
 }
 
@@ -86,156 +76,120 @@ pub fn collect_export_types() -> Vec<ExportType> {
         .unwrap()
 }
 
-pub fn configure_vst_export(plugin_name: &str) -> Result<Vst3Config> {
-    let vst_id: String = input("VST ID?")
-        .placeholder(DEFAULT_VST_ID)
-        .default_input(DEFAULT_VST_ID)
-        .validate(|input: &String| {
-            if input.len() != 16 {
-                Err("VST3 ID must be exactly 16 characters.")
-            } else {
-                Ok(())
-            }
-        })
-        .interact()?;
-    let sub_categories = build_category_list(
-        "Main VST Subcategory?",
-        vec!["Fx", "Instrument", "Spatial"],
-        "Other VST Subcategories?",
-        vec![
-            "Analyzer",
-            "Delay",
-            "Distortion",
-            "Drum",
-            "Dynamics",
-            "Eq",
-            "External",
-            "Filter",
-            "Generator",
-            "Mastering",
-            "Modulation",
-            "Network",
-            "Piano",
-            "PitchShift",
-            "Restoration",
-            "Reverb",
-            "Sampler",
-            "Synth",
-            "Tools",
-            "UpDownmix",
-        ],
-        "Vst3SubCategory",
-    )?;
+pub fn configure_vst_export(plugin_name: &str, defaults: bool) -> Result<Vst3Config> {
+    let answers = ask_builtin(VST3_PROMPTS, defaults)?;
     Ok(Vst3Config {
         plugin_name: plugin_name.to_string(),
-        vst_id,
-        sub_categories,
+        vst_id: answers.get("vst_id")?,
+        sub_categories: answers.get("sub_categories")?,
     })
 }
 
-pub fn configure_clap_export(plugin_name: &str) -> Result<ClapConfig> {
-    // clap id
-    let clap_id: String = input("CLAP ID?")
-        .placeholder(DEFAULT_CLAP_ID)
-        .default_input(DEFAULT_CLAP_ID)
-        .interact()?;
-
-    // clap description
-    let clap_description: String = input("CLAP ID?")
-        .placeholder(DEFAULT_CLAP_DESCRIPTION)
-        .default_input(DEFAULT_CLAP_DESCRIPTION)
-        .interact()?;
-
-    // clap features
-    let clap_features = build_category_list(
-        "Main CLAP Feature?",
-        vec!["Instrument", "AudioEffect", "NoteDetector", "NoteEffect"],
-        "Other CLAP Features?",
-        vec![
-            "Analyzer",
-            "Synthesizer",
-            "Sampler",
-            "Drum",
-            "DrumMachine",
-            "Filter",
-            "Phaser",
-            "Equalizer",
-            "Deesser",
-            "PhaseVocoder",
-            "Granular",
-            "FrequencyShifter",
-            "PitchShifter",
-            "Distortion",
-            "TransientShaper",
-            "Compressor",
-            "Expander",
-            "Gate",
-            "Limiter",
-            "Flanger",
-            "Chorus",
-            "Delay",
-            "Reverb",
-            "Tremolo",
-            "Glitch",
-            "Utility",
-            "PitchCorrection",
-            "Restoration",
-            "MultiEffects",
-            "Mixing",
-            "Mastering",
-            "Mono",
-            "Stereo",
-            "Surround",
-            "Ambisonic",
-        ],
-        "ClapFeature",
-    )?;
-
+pub fn configure_clap_export(plugin_name: &str, defaults: bool) -> Result<ClapConfig> {
+    let answers = ask_builtin(CLAP_PROMPTS, defaults)?;
     Ok(ClapConfig {
         plugin_name: plugin_name.to_string(),
-        clap_id,
-        clap_description,
-        clap_features: clap_features.to_owned(),
+        clap_id: answers.get("clap_id")?,
+        clap_description: answers.get("clap_description")?,
+        clap_features: answers.get("clap_features")?,
     })
 }
 
-// why did i document this so much??
+/// The CLAP config of a plugin that isn't exported as CLAP. Its impl only exists for the tests,
+/// so it gets an ID of its own instead of the default one.
+pub fn configure_test_clap(crate_name: &str, plugin_name: &str) -> Result<ClapConfig> {
+    Ok(ClapConfig {
+        clap_id: format!("{}.{}.tests", crate_name, plugin_name.to_lowercase()),
+        ..configure_clap_export(plugin_name, true)?
+    })
+}
 
-/// Creates a `select` and `multi-select` for a main category and optional categories.
-/// Returns a list of categories as a string, used for VST3 Subcategories and CLAP features.
-/// ## Parameters
-/// - `main_category_prompt`: A prompt that the user will see for the main, required category.
-/// - `main_category_list`: This vec will be used as the options to build a `select` for the main category.
-/// - `multi_select_prompt`: A prompt that the user will see for the remaining optional categories.
-/// - `other_categories_list`: This vec will be used as the options to build a `multi-select` for the optional categories.
-/// - `enum_prefix`: When the final list is stringified, you may prepend a string version of an enum to each category.
-fn build_category_list(
-    main_category_prompt: &str,
-    main_category_list: Vec<&str>,
-    multi_select_prompt: &str,
-    other_categories_list: Vec<&str>,
-    enum_prefix: &str,
-) -> Result<String> {
-    // main, required category
-    let mut main_category_select = select(main_category_prompt);
-    for item in main_category_list {
-        main_category_select = main_category_select.item(item, item, "");
+/// Asks every prompt declared by a template manifest, in order, and computes its derived values.
+/// Prompts whose `when` condition doesn't hold are skipped.
+/// With `defaults` set, nothing is asked and each prompt's default is used instead.
+pub fn ask_prompts(manifest: &TemplateManifest, defaults: bool) -> Result<Answers> {
+    let mut answers = Answers::default();
+    for prompt in &manifest.prompts {
+        if prompt
+            .when
+            .as_ref()
+            .is_some_and(|when| !answers.matches(when))
+        {
+            continue;
+        }
+        let answer = if defaults {
+            match &prompt.default {
+                Some(default) => default.clone(),
+                None => bail!("The prompt \"{}\" has no default value", prompt.name),
+            }
+        } else {
+            ask(prompt)?
+        };
+
+        // prefixes only apply to the rendered answer, conditions still see the raw values
+        let rendered = match &answer {
+            Answer::Text(value) if prompt.kind() == PromptKind::Select => {
+                format!("{}{}", prompt.prefix, value)
+            }
+            Answer::List(values) => values
+                .iter()
+                .map(|value| format!("{}{}", prompt.prefix, value))
+                .collect::<Vec<_>>()
+                .join(", "),
+            Answer::Text(value) => value.clone(),
+            Answer::Bool(value) => value.to_string(),
+        };
+        answers.insert(&prompt.name, answer, rendered);
     }
-    let main_category = main_category_select.interact()?;
+    answers.derive(&manifest.derived)?;
+    Ok(answers)
+}
 
-    // other, optional categories
-    let mut multi_builder = multiselect(multi_select_prompt);
-    for cat in other_categories_list {
-        multi_builder = multi_builder.item(cat, cat, "");
-    }
-    let mut other_categories = multi_builder.required(false).interact()?;
-    // add main category to beginning of list
-    other_categories.insert(0, main_category);
-    let concat_items: Vec<_> = other_categories
-        .iter()
-        // prepend an enum
-        .map(|feature| format!("{}::{}", enum_prefix, feature))
-        .collect();
-    // done!!
-    Ok(concat_items.join(", "))
+/// Drives cliclack for a single prompt.
+fn ask(prompt: &Prompt) -> Result<Answer> {
+    let answer = match prompt.kind() {
+        PromptKind::Text => {
+            let mut text_input = input(&prompt.prompt);
+            if let Some(Answer::Text(default)) = &prompt.default {
+                text_input = text_input.placeholder(default).default_input(default);
+            } else {
+                text_input = text_input.required(true);
+            }
+            if let Some(validator) = prompt.validator()? {
+                text_input = text_input.validate(move |input: &String| validator.check(input));
+            }
+            Answer::Text(text_input.interact()?)
+        }
+        PromptKind::Select => {
+            let mut choice_select = select(&prompt.prompt);
+            for choice in &prompt.choices {
+                choice_select =
+                    choice_select.item(choice.value().to_owned(), choice.label(), choice.hint());
+            }
+            if let Some(Answer::Text(default)) = &prompt.default {
+                choice_select = choice_select.initial_value(default.clone());
+            }
+            Answer::Text(choice_select.interact()?)
+        }
+        PromptKind::Multiselect => {
+            let mut multi_builder = multiselect(&prompt.prompt);
+            for choice in &prompt.choices {
+                multi_builder =
+                    multi_builder.item(choice.value().to_owned(), choice.label(), choice.hint());
+            }
+            if let Some(Answer::List(defaults)) = &prompt.default {
+                multi_builder = multi_builder.initial_values(defaults.clone());
+            }
+            Answer::List(multi_builder.required(prompt.required).interact()?)
+        }
+        PromptKind::Confirm => {
+            let initial_value = matches!(prompt.default, Some(Answer::Bool(true)));
+            Answer::Bool(
+                confirm(&prompt.prompt)
+                    .initial_value(initial_value)
+                    .interact()?,
+            )
+        }
+    };
+    Ok(answer)
 }
@@ -1,115 +1,240 @@
-use super::boilerplate::{ClapConfig, LibConfig, StandaloneConfig, Vst3Config};
-use anyhow::Result;
-use std::io::Read;
-use std::path::Path;
+use super::boilerplate::{BenchConfig, ClapConfig, ClapTestsConfig, DspConfig, LibConfig};
+use super::boilerplate::{PresetsConfig, StandaloneConfig, TestsConfig, Vst3Config};
+use super::boilerplate::{SplitDspConfig, SplitEditorConfig, SplitLibConfig, SplitParamsConfig};
+use super::template::{render_str, Templates};
+use crate::bundle::plugin_packages;
+use crate::cargo_toml::CargoToml;
+use crate::cli::CiProvider;
+use crate::doctor::apt_packages;
+use crate::rust_file::{rust_files, RustFile};
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{fs::File, io::Write};
-use toml::Table;
-use toml::Value::String as TomlString;
-use toml::Value::{Array, Table as VTable};
+use toml_edit::{value, Array, InlineTable};
+
+/// Compiles the test-only `ClapPlugin` impl of plugins that aren't exported as CLAP.
+pub const CLAP_TESTS_FEATURE: &str = "clap-tests";
+
+pub const NIH_PLUG_GIT: &str = "https://github.com/robbert-vdh/nih-plug.git";
+// the test host has nothing to fill in, so it's copied as-is
+const TEST_HOST: &str = include_str!("../../templates/test_host.txt");
+// GitHub's `${{ }}` expressions would clash with the template syntax, so these use `@NAME@` instead
+const GITHUB_CI: &str = include_str!("../../templates/github_ci.yml");
+const GITLAB_CI: &str = include_str!("../../templates/gitlab_ci.yml");
+const GPL_3_LICENSE: &str = include_str!("../../templates/licenses/GPL-3.0.txt");
+const ISC_LICENSE: &str = include_str!("../../templates/licenses/ISC.txt");
+const MIT_LICENSE: &str = include_str!("../../templates/licenses/MIT.txt");
+const PROPRIETARY_LICENSE: &str = include_str!("../../templates/licenses/Proprietary.txt");
 
 // i may have overcomplicated this part by quite a lot,
 // but eh
 // an easier thing to do would have been to use a templated Cargo.toml file or something.
 
 /// Opens an existing Cargo.toml file, adds the `nih_plug` crate (with the github link),
-/// and adds the `cdylib` crate type.
-pub fn write_to_toml<P: AsRef<Path>>(standalone: bool, project_path: P) -> Result<()> {
-    // TODO:
-    // figure out how to deal with all of these unwrap() calls
-    //
-    // prereq: open file, read into a string, and parse the string with toml
-    let mut file_read = File::options()
-        .read(true)
-        .open(project_path.as_ref().join("Cargo.toml"))?;
-    let mut str_contents = String::new();
-    file_read.read_to_string(&mut str_contents)?;
-    let mut value = str_contents.parse::<toml::Table>()?;
+/// and adds the `cdylib` crate type (plus `lib`, so that `tests/` can use the plugin).
+/// The CLAP tests also get `clap-sys` as a dev-dependency.
+/// Inside a workspace, `nih_plug` is inherited from `[workspace.dependencies]` instead.
+pub fn write_to_toml<P: AsRef<Path>>(
+    standalone: bool,
+    in_workspace: bool,
+    clap_export: bool,
+    project_path: P,
+) -> Result<()> {
+    let mut manifest = CargoToml::open(project_path)?;
+    let package_name = manifest
+        .table_mut("package")?
+        .get("name")
+        .and_then(|name| name.as_str())
+        .context("Cargo.toml has no package name")?
+        .to_owned();
 
     // 1. add nih_plug as a dependency
-    let dependencies = value
-        .get_mut("dependencies")
-        .unwrap()
-        .as_table_mut()
-        .unwrap();
-
-    add_nih_plug(dependencies, standalone);
+    let dependencies = manifest.table_mut("dependencies")?;
+    dependencies.insert(
+        "nih_plug",
+        value(nih_plug_dependency(standalone, in_workspace)),
+    );
 
-    let mut crate_type = vec![TomlString("cdylib".to_owned())];
-    if standalone {
-        crate_type.push(TomlString("lib".to_owned()));
+    // the tests load the plugin through a tiny CLAP host
+    let dev_dependencies = manifest.table_mut("dev-dependencies")?;
+    dev_dependencies.insert("clap-sys", value("0.5"));
+    // without a CLAP export, the `ClapPlugin` impl is behind a feature, which only the tests
+    // enable by depending on the crate itself
+    if !clap_export {
+        let mut features = Array::new();
+        features.push(CLAP_TESTS_FEATURE);
+        let mut this_crate = InlineTable::new();
+        this_crate.insert("path", ".".into());
+        this_crate.insert("features", features.into());
+        dev_dependencies.insert(&package_name, value(this_crate));
+        manifest
+            .table_mut("features")?
+            .insert(CLAP_TESTS_FEATURE, value(Array::new()));
     }
 
+    let mut crate_type = Array::new();
+    crate_type.push("cdylib");
+    crate_type.push("lib");
+
     // 2. declare that this is a cdylib
-    let mut crate_type_table = toml::Table::new();
-    crate_type_table.insert("crate-type".to_owned(), Array(crate_type));
-    value.insert("lib".to_owned(), VTable(crate_type_table));
+    manifest
+        .table_mut("lib")?
+        .insert("crate-type", value(crate_type));
 
     // write it all back out
-    let new_str = toml::to_string(&value).unwrap();
-    // we must do this again to use truncate.
-    // TODO: don't open file twice i guess
-    let mut file_write = File::options()
-        .truncate(true)
-        .write(true)
-        .open(project_path.as_ref().join("Cargo.toml"))
-        .unwrap();
-
-    file_write.write_all(new_str.as_bytes()).unwrap();
-    Ok(())
+    manifest.save()
 }
 
-fn add_nih_plug(dependencies: &mut Table, standalone: bool) {
-    let mut nih_plug_table = toml::Table::new();
-    nih_plug_table.insert(
-        "git".to_owned(),
-        TomlString("https://github.com/robbert-vdh/nih-plug.git".to_owned()),
-    );
+fn nih_plug_dependency(standalone: bool, in_workspace: bool) -> InlineTable {
+    let mut nih_plug_table = InlineTable::new();
+    let mut features = Array::new();
 
-    // program will panic if allocation occurs on the process thread
-    // we want this feature no matter what
-    let mut features_vec = vec![TomlString("assert_process_allocs".to_owned())];
+    if in_workspace {
+        // the git link and assert_process_allocs come from the workspace
+        nih_plug_table.insert("workspace", true.into());
+    } else {
+        nih_plug_table.insert("git", NIH_PLUG_GIT.into());
+        // program will panic if allocation occurs on the process thread
+        // we want this feature no matter what
+        features.push("assert_process_allocs");
+    }
 
     // unlike assert_process_allocs above, we only include this feature if the user wants
     if standalone {
-        features_vec.push(TomlString("standalone".to_owned()));
+        features.push("standalone");
+    }
+    if !features.is_empty() {
+        nih_plug_table.insert("features", features.into());
     }
-    nih_plug_table.insert("features".to_owned(), Array(features_vec));
-    dependencies.insert("nih_plug".to_owned(), VTable(nih_plug_table));
+    nih_plug_table
+}
+
+/// Creates a new, empty workspace at `root`, with `nih_plug` as a shared dependency.
+/// Plugin crates are added to `plugins/` as members later on.
+pub fn create_workspace<P: AsRef<Path>>(root: P) -> Result<()> {
+    let root = root.as_ref();
+    create_dir_all(root.join("plugins"))?;
+    File::create(root.join("Cargo.toml"))?;
+
+    let mut manifest = CargoToml::open(root)?;
+    let workspace = manifest.table_mut("workspace")?;
+    workspace.set_implicit(false);
+    workspace.insert("members", value(Array::new()));
+    workspace.insert("resolver", value("2"));
+    add_workspace_nih_plug(&mut manifest)?;
+    manifest.save()?;
+
+    let mut gitignore = File::create(root.join(".gitignore"))?;
+    gitignore.write_all(b"/target\n")?;
+    git_init(root);
+    Ok(())
+}
+
+/// Makes sure the workspace shares a `nih_plug` dependency, without touching an existing one.
+pub fn add_workspace_nih_plug(manifest: &mut CargoToml) -> Result<()> {
+    let dependencies = manifest.table_mut("workspace.dependencies")?;
+    if !dependencies.contains_key("nih_plug") {
+        dependencies.insert("nih_plug", value(nih_plug_dependency(false, false)));
+    }
+    Ok(())
+}
+
+/// Creates a plain, `no_std` library crate in the workspace's `libs/` directory,
+/// and adds it as a path dependency of every plugin crate in the workspace.
+pub fn create_lib_crate(root: &Path, templates: &Templates, lib_name: &str) -> Result<()> {
+    let member = format!("libs/{}", lib_name);
+    let path = root.join(&member);
+    if path.exists() {
+        bail!("The directory \"{}\" already exists", path.display());
+    }
+    cargo_new(&path, true);
+
+    // floating point math isn't in `core`, so the skeleton uses libm instead of std
+    let mut manifest = CargoToml::open(&path)?;
+    manifest
+        .table_mut("dependencies")?
+        .insert("libm", value("0.2"));
+    manifest.save()?;
+
+    let config = DspConfig {
+        lib_name: lib_name.to_owned(),
+    };
+    write(path.join("src").join("lib.rs"), templates.render(&config)?)?;
+
+    // the workspace owns the path, plugins just inherit it
+    let mut workspace = CargoToml::open(root)?;
+    workspace.add_workspace_member(&member)?;
+    let mut dependency = InlineTable::new();
+    dependency.insert("path", member.into());
+    workspace
+        .table_mut("workspace.dependencies")?
+        .insert(lib_name, value(dependency));
+    workspace.save()?;
+
+    let metadata = MetadataCommand::new()
+        .manifest_path(root.join("Cargo.toml"))
+        .no_deps()
+        .exec()?;
+    for package in plugin_packages(&metadata) {
+        let Some(plugin_dir) = package.manifest_path.parent() else {
+            continue;
+        };
+        let mut plugin = CargoToml::open(plugin_dir)?;
+        plugin
+            .table_mut("dependencies")?
+            .insert(lib_name, value(workspace_dependency()));
+        plugin.save()?;
+    }
+    Ok(())
+}
+
+/// `{ workspace = true }`, for dependencies inherited from `[workspace.dependencies]`.
+pub fn workspace_dependency() -> InlineTable {
+    let mut dependency = InlineTable::new();
+    dependency.insert("workspace", true.into());
+    dependency
 }
 
 pub fn write_to_main<P: AsRef<Path>>(
     project_path: P,
+    templates: &Templates,
     standalone_config: Option<StandaloneConfig>,
 ) -> Result<()> {
     // 99.9% sure that create() is ok since the file probably won't already exist
     if let Some(main) = standalone_config {
         let mut main_file = File::create(project_path.as_ref().join("src").join("main.rs"))?;
-        main_file.write_all(main.to_string().as_bytes())?;
+        main_file.write_all(templates.render(&main)?.as_bytes())?;
     }
     Ok(())
 }
 
 /// Takes user input and generates a lib.rs file.
 /// The user input includes general plugin information, as well as optional CLAP info.
+/// Each part is rendered with a user template if one is found, otherwise with the built-in one.
+/// Without `clap_export`, the CLAP impl is only there for the tests.
 pub fn write_to_lib<P: AsRef<Path>>(
     project_path: P,
+    templates: &Templates,
     lib_config: &LibConfig,
     clap_config: Option<ClapConfig>,
+    clap_export: bool,
     vst_config: Option<Vst3Config>,
 ) -> Result<()> {
     // now we're going to generate our lib.rs file from our template and overwrite the existing lib.rs
     let lib_path = project_path.as_ref().join("src").join("lib.rs");
     let mut lib_file = File::options().write(true).open(lib_path)?;
-    let mut output = lib_config.to_string();
+    let mut output = templates.render(lib_config)?;
 
     // if the user configured CLAP, add it to the file.
     if let Some(data) = clap_config {
-        output.push_str(&data.to_string());
+        output.push_str(&render_clap(templates, &data, clap_export)?);
     }
     // if the user configured CLAP, add it to the file.
     if let Some(data) = vst_config {
-        output.push_str(&data.to_string());
+        output.push_str(&templates.render(&data)?);
     }
 
     lib_file.write_all(output.as_bytes())?;
@@ -117,12 +242,445 @@ pub fn write_to_lib<P: AsRef<Path>>(
     Ok(())
 }
 
-/// Executes the `cargo new` command, creating a new project.
-/// **NOTE**: this function creates the new project *with a git repo* (via `--vcs git`)
-pub fn cargo_new(project_name: &str) {
+/// Renders the `ClapPlugin` impl. Without `export`, the `nih_export_clap!` is left out, since the
+/// plugin doesn't ship as CLAP: the impl is only compiled with the `clap-tests` feature, so that
+/// the tests can export it themselves.
+fn render_clap(templates: &Templates, config: &ClapConfig, export: bool) -> Result<String> {
+    let rendered = templates.render(config)?;
+    if export {
+        return Ok(rendered);
+    }
+    let mut impl_only = String::new();
+    for line in rendered.split_inclusive('\n') {
+        if line.starts_with("nih_export_clap!") {
+            continue;
+        }
+        if line.starts_with("impl ") {
+            impl_only.push_str(&format!("#[cfg(feature = \"{}\")]\n", CLAP_TESTS_FEATURE));
+        }
+        impl_only.push_str(line);
+    }
+    Ok(format!(
+        "// Only compiled for tests/clap.rs, which loads the plugin through CLAP.\n\
+         // Add `nih_export_clap!({});` and drop the `cfg` to ship it as a CLAP plugin too.\n{}\n\n",
+        config.plugin_name,
+        impl_only.trim_end()
+    ))
+}
+
+/// Like `write_to_lib`, but splits the plugin into modules: `params.rs`, `dsp.rs`, `editor.rs`,
+/// and `formats.rs` for the CLAP/VST3 impls. `lib.rs` keeps the `Plugin` impl, the `mod`
+/// declarations, and the export macros, which have to stay at the crate root.
+pub fn write_split_lib(
+    project_path: &Path,
+    templates: &Templates,
+    lib_config: &LibConfig,
+    clap_config: Option<ClapConfig>,
+    clap_export: bool,
+    vst_config: Option<Vst3Config>,
+) -> Result<()> {
+    let src = project_path.join("src");
+    let plugin_name = lib_config.plugin_name.clone();
+    write(
+        src.join("params.rs"),
+        templates.render(&SplitParamsConfig {
+            plugin_name: plugin_name.clone(),
+        })?,
+    )?;
+    write(
+        src.join("dsp.rs"),
+        templates.render(&SplitDspConfig {
+            plugin_name: plugin_name.clone(),
+        })?,
+    )?;
+    write(
+        src.join("editor.rs"),
+        templates.render(&SplitEditorConfig {
+            plugin_name: plugin_name.clone(),
+        })?,
+    )?;
+
+    // the format templates end with their export macro, which moves to lib.rs
+    let mut formats = String::new();
+    let mut exports = String::new();
+    for rendered in [
+        clap_config
+            .map(|data| render_clap(templates, &data, clap_export))
+            .transpose()?,
+        vst_config.map(|data| templates.render(&data)).transpose()?,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        for line in rendered.split_inclusive('\n') {
+            if line.starts_with("nih_export_") {
+                exports.push_str(line);
+            } else {
+                formats.push_str(line);
+            }
+        }
+    }
+
+    let mut modules = vec!["dsp", "editor", "params"];
+    if !formats.is_empty() {
+        modules.insert(2, "formats");
+        write(
+            src.join("formats.rs"),
+            format!(
+                "use crate::{};\nuse nih_plug::prelude::*;\n\n{}\n",
+                plugin_name,
+                formats.trim()
+            ),
+        )?;
+    }
+
+    let mut lib = String::new();
+    for module in modules {
+        lib.push_str(&format!("mod {};\n", module));
+    }
+    lib.push('\n');
+    lib.push_str(&templates.render(&SplitLibConfig {
+        plugin_name,
+        vendor: lib_config.vendor.clone(),
+        url: lib_config.url.clone(),
+        email: lib_config.email.clone(),
+        midi_config: lib_config.midi_config.clone(),
+        midi_output: lib_config.midi_output.clone(),
+    })?);
+    if !exports.is_empty() {
+        lib = format!("{}\n\n{}", lib.trim_end(), exports);
+    }
+    write(src.join("lib.rs"), lib)?;
+    Ok(())
+}
+
+/// Whether code under `license` may use nih_plug's VST3 bindings, which are GPLv3.
+pub fn allows_vst3(license: &str) -> bool {
+    license.starts_with("GPL-3.0")
+}
+
+/// Writes the LICENSE file, and sets `license` in Cargo.toml.
+/// Proprietary code gets `license-file` instead (there's no SPDX identifier for it), and isn't published.
+pub fn write_license(project_path: &Path, license: &str, vendor: &str) -> Result<()> {
+    let text = match license {
+        "GPL-3.0-or-later" | "GPL-3.0-only" => GPL_3_LICENSE,
+        "ISC" => ISC_LICENSE,
+        "MIT" => MIT_LICENSE,
+        "Proprietary" => PROPRIETARY_LICENSE,
+        other => bail!("There's no license text for {}", other),
+    };
+    let variables = [
+        ("year", chrono::Local::now().format("%Y").to_string()),
+        ("vendor", vendor.to_owned()),
+    ];
+    write(project_path.join("LICENSE"), render_str(text, &variables)?)?;
+
+    let mut manifest = CargoToml::open(project_path)?;
+    let package = manifest.table_mut("package")?;
+    if license == "Proprietary" {
+        package.insert("license-file", value("LICENSE"));
+        package.insert("publish", value(false));
+    } else {
+        package.insert("license", value(license));
+    }
+    manifest.save()
+}
+
+/// Every Rust file in a project's `src`, `tests` and `benches`.
+pub fn project_rust_files(project_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for dir in ["src", "tests", "benches"] {
+        files.extend(rust_files(&project_path.join(dir))?);
+    }
+    Ok(files)
+}
+
+/// Adds an SPDX license header to each of the (generated) `files` that doesn't have one yet.
+/// The user's own files are left alone, so only pass in what npcli just wrote.
+pub fn write_license_headers(files: &[PathBuf], license: &str) -> Result<()> {
+    let spdx_id = if license == "Proprietary" {
+        "LicenseRef-Proprietary"
+    } else {
+        license
+    };
+    let header = format!("// SPDX-License-Identifier: {}\n\n", spdx_id);
+    for file in files {
+        let contents = read_to_string(file)?;
+        if !contents.contains("SPDX-License-Identifier") {
+            write(file, format!("{}{}", header, contents))?;
+        }
+    }
+    Ok(())
+}
+
+/// The export macros of an existing crate, and the file they're in (usually `lib.rs`).
+pub struct Exports {
+    pub file: PathBuf,
+    pub clap: Vec<String>,
+    pub vst3: Vec<String>,
+}
+
+/// Finds the `nih_export_clap!`/`nih_export_vst3!` calls in the crate's source files.
+pub fn find_exports(project_path: &Path) -> Result<Exports> {
+    let file = RustFile::find(&project_path.join("src"), |file| {
+        ["nih_export_clap", "nih_export_vst3"]
+            .iter()
+            .any(|name| file.macro_args(name).is_ok_and(|args| args.is_some()))
+    })?
+    .context("No `nih_export_clap!` or `nih_export_vst3!` was found in src/")?;
+    Ok(Exports {
+        file: file.path().to_path_buf(),
+        clap: file.macro_args("nih_export_clap")?.unwrap_or_default(),
+        vst3: file.macro_args("nih_export_vst3")?.unwrap_or_default(),
+    })
+}
+
+/// Returns the value of a string const in the crate's `impl Plugin`, e.g. the existing plugin's `VENDOR`.
+pub fn find_str_const(project_path: &Path, name: &str) -> Result<Option<String>> {
+    let plugin = RustFile::find(&project_path.join("src"), |file| {
+        file.impl_type("Plugin").is_ok_and(|ty| ty.is_some())
+    })?;
+    match plugin {
+        Some(file) => file.str_const("Plugin", None, name),
+        None => Ok(None),
+    }
+}
+
+/// Writes another plugin to its own module (e.g. `src/my_reverb.rs`), declares the module next to
+/// the export macros, and adds the plugin to them. nih_plug's export macros take a list of plugins,
+/// which is how one library exports several.
+/// Without `clap_export`, the CLAP impl is only there for the tests, like in `write_to_lib`.
+pub fn write_plugin_module(
+    project_path: &Path,
+    templates: &Templates,
+    lib_config: &LibConfig,
+    clap_config: Option<ClapConfig>,
+    clap_export: bool,
+    vst_config: Option<Vst3Config>,
+) -> Result<PathBuf> {
+    let exports = find_exports(project_path)?;
+    let plugin_name = &lib_config.plugin_name;
+    if exports.clap.contains(plugin_name) || exports.vst3.contains(plugin_name) {
+        bail!("{} is already exported", plugin_name);
+    }
+    let module = to_snake_case(plugin_name);
+    let module_path = project_path.join("src").join(format!("{}.rs", module));
+    if module_path.exists() {
+        bail!("\"{}\" already exists", module_path.display());
+    }
+
+    let mut output = templates.render(lib_config)?;
+    if let Some(data) = clap_config {
+        output.push_str(&render_clap(templates, &data, clap_export)?);
+    }
+    if let Some(data) = vst_config {
+        output.push_str(&templates.render(&data)?);
+    }
+    // the templates export a single plugin, but here the crate's export macros do that
+    let output: String = output
+        .split_inclusive('\n')
+        .filter(|line| !line.starts_with("nih_export_"))
+        .collect();
+    write(&module_path, output.trim_end().to_owned() + "\n")?;
+
+    // both export macros have to be in the same file, since that file declares the module
+    let mut file = RustFile::open(&exports.file)?;
+    for (name, plugins) in [
+        ("nih_export_clap", &exports.clap),
+        ("nih_export_vst3", &exports.vst3),
+    ] {
+        if !plugins.is_empty() {
+            let mut plugins = plugins.clone();
+            plugins.push(plugin_name.clone());
+            file.set_macro_args(name, &plugins)?;
+        }
+    }
+    file.add_items_after_imports(&format!("mod {0};\npub use {0}::{1};", module, plugin_name))?;
+    file.save()?;
+    Ok(module_path)
+}
+
+/// Renders the factory preset module, for `src/presets.rs`.
+pub fn render_presets_module(templates: &Templates, plugin_name: &str) -> Result<String> {
+    templates.render(&PresetsConfig {
+        plugin_name: plugin_name.to_owned(),
+    })
+}
+
+/// `MyReverb` -> `my_reverb`
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Generates the `tests/` directory: tests that use the plugin directly, and tests that process
+/// audio and save/load state through a tiny CLAP host. When the plugin doesn't export CLAP
+/// (`clap_export`), the tests export its `ClapPlugin` impl themselves.
+pub fn write_tests<P: AsRef<Path>>(
+    project_path: P,
+    templates: &Templates,
+    project_name: &str,
+    plugin_name: &str,
+    clap_export: bool,
+) -> Result<()> {
+    let tests_path = project_path.as_ref().join("tests");
+    create_dir_all(&tests_path)?;
+    let crate_name = project_name.replace('-', "_");
+
+    let tests_config = TestsConfig {
+        crate_name: crate_name.clone(),
+        plugin_name: plugin_name.to_owned(),
+    };
+    write(
+        tests_path.join("plugin.rs"),
+        templates.render(&tests_config)?,
+    )?;
+    write_test_host(project_path.as_ref())?;
+    // nih_plug only sets params and restores state from inside a wrapper, so the tests go through one
+    let clap_entry = if clap_export {
+        format!("use {}::clap_entry;", crate_name)
+    } else {
+        format!(
+            "nih_plug::nih_export_clap!({}::{});",
+            crate_name, plugin_name
+        )
+    };
+    let clap_config = ClapTestsConfig {
+        crate_name,
+        clap_entry,
+    };
+    write(tests_path.join("clap.rs"), templates.render(&clap_config)?)?;
+    Ok(())
+}
+
+/// Writes the tiny CLAP host to `tests/host/mod.rs`, unless it's already there.
+/// It lives in a subdirectory so that cargo doesn't treat it as a test of its own.
+fn write_test_host(project_path: &Path) -> Result<()> {
+    let host_path = project_path.join("tests").join("host");
+    if host_path.join("mod.rs").exists() {
+        return Ok(());
+    }
+    create_dir_all(&host_path)?;
+    write(host_path.join("mod.rs"), TEST_HOST)?;
+    Ok(())
+}
+
+/// Adds a criterion benchmark of `process` to a plugin crate, in `benches/process.rs`.
+/// The benchmark runs the plugin through the same CLAP host as the tests.
+pub fn create_bench(project_path: &Path, templates: &Templates, package_name: &str) -> Result<()> {
+    let lib = read_to_string(project_path.join("src").join("lib.rs"))
+        .with_context(|| format!("Failed to read lib.rs of {}", package_name))?;
+    if !lib.contains("nih_export_clap!") {
+        bail!(
+            "The benchmark runs {} through its CLAP export, but it doesn't export CLAP",
+            package_name
+        );
+    }
+    let bench_path = project_path.join("benches").join("process.rs");
+    if bench_path.exists() {
+        bail!("\"{}\" already exists", bench_path.display());
+    }
+
+    write_test_host(project_path)?;
+    create_dir_all(project_path.join("benches"))?;
+    let config = BenchConfig {
+        crate_name: package_name.replace('-', "_"),
+    };
+    write(&bench_path, templates.render(&config)?)?;
+
+    let mut manifest = CargoToml::open(project_path)?;
+    let dev_dependencies = manifest.table_mut("dev-dependencies")?;
+    dev_dependencies
+        .entry("criterion")
+        .or_insert_with(|| value("0.5"));
+    dev_dependencies
+        .entry("clap-sys")
+        .or_insert_with(|| value("0.5"));
+    manifest.add_bench("process")?;
+    // projects from before `tests/` existed are only a cdylib, which benchmarks can't link to
+    if let Some(crate_type) = manifest
+        .table_mut("lib")?
+        .get_mut("crate-type")
+        .and_then(|crate_type| crate_type.as_array_mut())
+    {
+        if !crate_type.iter().any(|kind| kind.as_str() == Some("lib")) {
+            crate_type.push("lib");
+        }
+    }
+    manifest.save()
+}
+
+/// Writes a CI pipeline for the project or workspace at `root` and returns its path.
+/// The pipeline lints and tests everything, then bundles every plugin crate on Linux, Windows and macOS.
+/// The plugin crates are listed by name, so the pipeline has to be regenerated (`overwrite`) when one is added.
+pub fn write_ci(root: &Path, provider: CiProvider, overwrite: bool) -> Result<PathBuf> {
+    let path = ci_path(root, provider);
+    if path.exists() && !overwrite {
+        bail!("\"{}\" already exists", path.display());
+    }
+
+    let metadata = MetadataCommand::new().current_dir(root).no_deps().exec()?;
+    let packages: Vec<&str> = plugin_packages(&metadata)
+        .into_iter()
+        .map(|package| package.name.as_str())
+        .collect();
+    if packages.is_empty() {
+        bail!("No plugin crates were found. A plugin crate must have the `cdylib` crate type.");
+    }
+
+    let template = match provider {
+        CiProvider::Github => GITHUB_CI,
+        CiProvider::Gitlab => GITLAB_CI,
+    };
+    let pipeline = template
+        .replace("@PACKAGES@", &packages.join(" "))
+        .replace("@APT_PACKAGES@", &apt_packages().join(" "))
+        // the release that generated the pipeline, since npcli doesn't ship a Cargo.lock to pin
+        .replace("@NPCLI_TAG@", concat!("v", env!("CARGO_PKG_VERSION")));
+    if let Some(dir) = path.parent() {
+        create_dir_all(dir)?;
+    }
+    write(&path, pipeline)?;
+    Ok(path)
+}
+
+/// Where the provider expects the pipeline in the repo at `root`.
+pub fn ci_path(root: &Path, provider: CiProvider) -> PathBuf {
+    match provider {
+        CiProvider::Github => root.join(".github").join("workflows").join("ci.yml"),
+        CiProvider::Gitlab => root.join(".gitlab-ci.yml"),
+    }
+}
+
+/// Executes the `cargo new` command, creating a new project at `project_path`.
+/// **NOTE**: this function creates the new project *with a git repo* (via `--vcs git`),
+/// unless it's a workspace member, which already lives in the workspace's repo.
+pub fn cargo_new<P: AsRef<Path>>(project_path: P, in_workspace: bool) {
     // creates a new project with cargo
-    // TODO: make sure user has cargo installed
-    let command = format!("cargo new --lib {} --vcs git", project_name);
+    // (`npcli new` has already made sure that cargo is installed)
+    let vcs = if in_workspace { "none" } else { "git" };
+    let command = format!(
+        "cargo new --lib \"{}\" --vcs {}",
+        project_path.as_ref().display(),
+        vcs
+    );
+    exec_command(&command);
+}
+
+/// Initializes a git repo in an existing project directory,
+/// for projects that weren't created with `cargo new`.
+pub fn git_init<P: AsRef<Path>>(project_path: P) {
+    let command = format!("git init \"{}\"", project_path.as_ref().display());
     exec_command(&command);
 }
 
@@ -0,0 +1,568 @@
+// This module contains the template manifest, which lets a template declare its own prompts.
+
+use super::template::render_str;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::fs::{create_dir_all, read, read_to_string, write};
+use std::path::Path;
+
+/// The file a template's manifest is read from.
+pub const MANIFEST_FILENAME: &str = "npcli-template.toml";
+
+#[derive(Deserialize, Default)]
+pub struct TemplateManifest {
+    #[serde(default)]
+    pub template: TemplateInfo,
+    #[serde(default)]
+    pub prompts: Vec<Prompt>,
+    /// Values computed from earlier answers, once every prompt has been asked.
+    #[serde(default)]
+    pub derived: Vec<Derived>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct TemplateInfo {
+    pub description: Option<String>,
+    /// Paths (relative to the template root) that won't be copied into the project.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptKind {
+    Text,
+    Select,
+    Multiselect,
+    Confirm,
+}
+
+/// A single question to ask the user. The answer is available to templates as `{{self.<name>}}`.
+#[derive(Deserialize)]
+pub struct Prompt {
+    pub name: String,
+    pub prompt: String,
+    /// Defaults to `select` if there are choices, and `text` otherwise.
+    pub kind: Option<PromptKind>,
+    pub default: Option<Answer>,
+    /// The options for `select` and `multiselect` prompts.
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+    /// A regex that a `text` answer must match.
+    pub validate: Option<String>,
+    /// The message shown when `validate` doesn't match.
+    pub error: Option<String>,
+    /// For `multiselect`, whether at least one item must be picked.
+    #[serde(default)]
+    pub required: bool,
+    /// Prepended to every chosen value, e.g. `ClapFeature::`.
+    #[serde(default)]
+    pub prefix: String,
+    /// Only ask this prompt if the condition holds, see [`Condition`].
+    pub when: Option<Condition>,
+}
+
+impl Prompt {
+    /// The compiled `validate` regex, if the prompt has one.
+    pub fn validator(&self) -> Result<Option<Validator>> {
+        let Some(pattern) = &self.validate else {
+            return Ok(None);
+        };
+        let regex = Regex::new(pattern)
+            .with_context(|| format!("The prompt \"{}\" has an invalid regex", self.name))?;
+        let error = self
+            .error
+            .clone()
+            .unwrap_or_else(|| format!("The answer must match \"{}\"", pattern));
+        Ok(Some(Validator { regex, error }))
+    }
+
+    pub fn kind(&self) -> PromptKind {
+        self.kind.unwrap_or(if self.choices.is_empty() {
+            PromptKind::Text
+        } else {
+            PromptKind::Select
+        })
+    }
+}
+
+/// Checks `text` answers against a prompt's `validate` regex.
+pub struct Validator {
+    regex: Regex,
+    error: String,
+}
+
+impl Validator {
+    /// Returns the message to show if `answer` doesn't match.
+    pub fn check(&self, answer: &str) -> Result<(), String> {
+        if self.regex.is_match(answer) {
+            Ok(())
+        } else {
+            Err(self.error.clone())
+        }
+    }
+}
+
+/// A choice is either just a value, or a value with a label and hint.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Choice {
+    Value(String),
+    Full {
+        value: String,
+        label: Option<String>,
+        #[serde(default)]
+        hint: String,
+    },
+}
+
+impl Choice {
+    pub fn value(&self) -> &str {
+        match self {
+            Choice::Value(value) | Choice::Full { value, .. } => value,
+        }
+    }
+
+    pub fn label(&self) -> &str {
+        match self {
+            Choice::Full {
+                label: Some(label), ..
+            } => label,
+            _ => self.value(),
+        }
+    }
+
+    pub fn hint(&self) -> &str {
+        match self {
+            Choice::Full { hint, .. } => hint,
+            Choice::Value(_) => "",
+        }
+    }
+}
+
+/// A value computed from earlier answers. Either `value` is rendered as a template,
+/// or every non-empty answer in `join` is joined with `separator`.
+#[derive(Deserialize)]
+pub struct Derived {
+    pub name: String,
+    pub value: Option<String>,
+    #[serde(default)]
+    pub join: Vec<String>,
+    #[serde(default = "default_separator")]
+    pub separator: String,
+    pub when: Option<Condition>,
+}
+
+fn default_separator() -> String {
+    ", ".to_owned()
+}
+
+/// The answer to a prompt, or a prompt's default.
+#[derive(Deserialize, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Answer {
+    Bool(bool),
+    List(Vec<String>),
+    Text(String),
+}
+
+impl Answer {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Answer::Bool(value) => *value,
+            Answer::List(values) => !values.is_empty(),
+            Answer::Text(value) => !value.is_empty(),
+        }
+    }
+
+    /// How this answer is written into a template.
+    fn render(&self) -> String {
+        match self {
+            Answer::Bool(value) => value.to_string(),
+            Answer::List(values) => values.join(", "),
+            Answer::Text(value) => value.clone(),
+        }
+    }
+}
+
+/// A condition on an earlier answer. One of:
+/// - `name` / `!name`: the answer is (not) true, non-empty, or has items
+/// - `name == value` / `name != value`: the answer is (not) exactly `value`
+/// - `name contains value`: a `multiselect` answer includes `value`
+#[derive(Deserialize)]
+#[serde(try_from = "String")]
+pub enum Condition {
+    Truthy(String),
+    Falsy(String),
+    Equals(String, String),
+    NotEquals(String, String),
+    Contains(String, String),
+}
+
+impl TryFrom<String> for Condition {
+    type Error = String;
+
+    fn try_from(condition: String) -> Result<Self, Self::Error> {
+        let parts: Vec<_> = condition.split_whitespace().collect();
+        let condition = match parts.as_slice() {
+            [name] => match name.strip_prefix('!') {
+                Some(name) => Condition::Falsy(name.to_string()),
+                None => Condition::Truthy(name.to_string()),
+            },
+            [name, "==", value] => Condition::Equals(name.to_string(), value.to_string()),
+            [name, "!=", value] => Condition::NotEquals(name.to_string(), value.to_string()),
+            [name, "contains", value] => Condition::Contains(name.to_string(), value.to_string()),
+            _ => return Err(format!("Invalid condition \"{}\"", condition)),
+        };
+        Ok(condition)
+    }
+}
+
+/// Every answer so far, in the order they were given.
+#[derive(Default)]
+pub struct Answers {
+    // the raw answer is kept for conditions, the rendered one for templates
+    entries: Vec<(String, Answer, String)>,
+}
+
+impl Answers {
+    pub fn insert(&mut self, name: &str, answer: Answer, rendered: String) {
+        self.entries.retain(|(existing, _, _)| existing != name);
+        self.entries.push((name.to_owned(), answer, rendered));
+    }
+
+    fn raw(&self, name: &str) -> Option<&Answer> {
+        self.entries
+            .iter()
+            .find(|(existing, _, _)| existing == name)
+            .map(|(_, answer, _)| answer)
+    }
+
+    /// Returns the rendered answer, or an error if the prompt wasn't asked.
+    pub fn get(&self, name: &str) -> Result<String> {
+        self.entries
+            .iter()
+            .find(|(existing, _, _)| existing == name)
+            .map(|(_, _, rendered)| rendered.clone())
+            .with_context(|| format!("No answer for \"{}\"", name))
+    }
+
+    pub fn matches(&self, condition: &Condition) -> bool {
+        match condition {
+            Condition::Truthy(name) => self.raw(name).is_some_and(Answer::is_truthy),
+            Condition::Falsy(name) => !self.raw(name).is_some_and(Answer::is_truthy),
+            Condition::Equals(name, value) => {
+                self.raw(name).map(Answer::render).as_ref() == Some(value)
+            }
+            Condition::NotEquals(name, value) => {
+                self.raw(name).map(Answer::render).as_ref() != Some(value)
+            }
+            Condition::Contains(name, value) => match self.raw(name) {
+                Some(Answer::List(values)) => values.contains(value),
+                _ => false,
+            },
+        }
+    }
+
+    /// Computes each derived value whose condition holds.
+    pub fn derive(&mut self, derived: &[Derived]) -> Result<()> {
+        for value in derived {
+            if value.when.as_ref().is_some_and(|when| !self.matches(when)) {
+                continue;
+            }
+            let rendered = match &value.value {
+                Some(template) => render_str(template, &self.variables())?,
+                None => value
+                    .join
+                    .iter()
+                    .filter_map(|name| self.get(name).ok())
+                    .filter(|answer| !answer.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(&value.separator),
+            };
+            self.insert(&value.name, Answer::Text(rendered.clone()), rendered);
+        }
+        Ok(())
+    }
+
+    /// The answers as template variables.
+    pub fn variables(&self) -> Vec<(&str, String)> {
+        self.entries
+            .iter()
+            .map(|(name, _, rendered)| (name.as_str(), rendered.clone()))
+            .collect()
+    }
+}
+
+impl TemplateManifest {
+    /// Reads the manifest in the template's root directory.
+    /// A template without a manifest is fine, it just won't ask anything.
+    pub fn load(root: &Path) -> Result<Self> {
+        let path = root.join(MANIFEST_FILENAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = read_to_string(&path)?;
+        Self::parse(&contents).with_context(|| format!("Failed to parse \"{}\"", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        Ok(toml::from_str(contents)?)
+    }
+
+    /// Copies every file of the template into `project_path`, rendering text files
+    /// (and file names) with the given variables. Binary files are copied as-is.
+    pub fn render_into(
+        &self,
+        root: &Path,
+        project_path: &Path,
+        variables: &[(&str, String)],
+    ) -> Result<()> {
+        self.render_dir(root, root, project_path, variables)
+    }
+
+    fn render_dir(
+        &self,
+        root: &Path,
+        dir: &Path,
+        project_path: &Path,
+        variables: &[(&str, String)],
+    ) -> Result<()> {
+        for entry in dir.read_dir()? {
+            let path = entry?.path();
+            let relative = path.strip_prefix(root)?;
+            let relative_str = relative.to_string_lossy().replace('\\', "/");
+
+            if relative_str == ".git"
+                || relative_str == MANIFEST_FILENAME
+                || self.template.exclude.contains(&relative_str)
+            {
+                continue;
+            }
+
+            if path.is_dir() {
+                self.render_dir(root, &path, project_path, variables)?;
+                continue;
+            }
+
+            let target = project_path.join(render_str(&relative_str, variables)?);
+            if let Some(parent) = target.parent() {
+                create_dir_all(parent)?;
+            }
+            let bytes = read(&path)?;
+            match String::from_utf8(bytes) {
+                Ok(text) => write(
+                    &target,
+                    render_str(&text, variables).with_context(|| {
+                        format!("Failed to render template \"{}\"", path.display())
+                    })?,
+                )?,
+                Err(err) => write(&target, err.into_bytes())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn answers() -> Answers {
+        let mut answers = Answers::default();
+        answers.insert("stereo", Answer::Bool(true), "true".to_owned());
+        answers.insert("notes", Answer::Text(String::new()), String::new());
+        answers.insert(
+            "midi_config",
+            Answer::Text("Basic".to_owned()),
+            "Basic".to_owned(),
+        );
+        answers.insert(
+            "features",
+            Answer::List(vec!["Synthesizer".to_owned(), "Stereo".to_owned()]),
+            "Synthesizer, Stereo".to_owned(),
+        );
+        answers
+    }
+
+    fn holds(condition: &str) -> bool {
+        let condition = Condition::try_from(condition.to_owned()).unwrap();
+        answers().matches(&condition)
+    }
+
+    #[test]
+    fn checks_truthy_conditions() {
+        assert!(holds("stereo"));
+        assert!(holds("features"));
+        assert!(!holds("notes"));
+        assert!(!holds("missing"));
+    }
+
+    #[test]
+    fn checks_falsy_conditions() {
+        assert!(!holds("!stereo"));
+        assert!(holds("!notes"));
+        assert!(holds("!missing"));
+    }
+
+    #[test]
+    fn checks_equality_conditions() {
+        assert!(holds("midi_config == Basic"));
+        assert!(!holds("midi_config == None"));
+        assert!(holds("stereo == true"));
+        assert!(holds("midi_config != None"));
+        assert!(!holds("midi_config != Basic"));
+        assert!(holds("missing != None"));
+    }
+
+    #[test]
+    fn checks_contains_conditions() {
+        assert!(holds("features contains Synthesizer"));
+        assert!(!holds("features contains Mono"));
+        // only multiselect answers contain anything
+        assert!(!holds("midi_config contains Basic"));
+    }
+
+    #[test]
+    fn rejects_invalid_conditions() {
+        assert!(Condition::try_from("midi_config is Basic".to_owned()).is_err());
+        assert!(Condition::try_from(String::new()).is_err());
+    }
+
+    #[test]
+    fn insert_replaces_answers_by_name() {
+        let mut answers = answers();
+        answers.insert("stereo", Answer::Bool(false), "false".to_owned());
+        assert_eq!(answers.get("stereo").unwrap(), "false");
+        assert_eq!(
+            answers
+                .variables()
+                .iter()
+                .filter(|(name, _)| *name == "stereo")
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn later_derived_values_override_earlier_ones() {
+        let manifest = TemplateManifest::parse(
+            r#"
+            [[derived]]
+            name = "channels"
+            value = "mono"
+
+            [[derived]]
+            name = "channels"
+            value = "stereo"
+            when = "stereo"
+
+            [[derived]]
+            name = "channels"
+            value = "surround"
+            when = "!stereo"
+
+            [[derived]]
+            name = "summary"
+            value = "{{self.midi_config}} in {{self.channels}}"
+
+            [[derived]]
+            name = "categories"
+            join = ["midi_config", "notes", "features"]
+            separator = " | "
+            "#,
+        )
+        .unwrap();
+        let mut answers = answers();
+        answers.derive(&manifest.derived).unwrap();
+        assert_eq!(answers.get("channels").unwrap(), "stereo");
+        assert_eq!(answers.get("summary").unwrap(), "Basic in stereo");
+        assert_eq!(
+            answers.get("categories").unwrap(),
+            "Basic | Synthesizer, Stereo"
+        );
+    }
+
+    #[test]
+    fn validates_text_answers() {
+        let manifest = TemplateManifest::parse(
+            r#"
+            [[prompts]]
+            name = "plugin_name"
+            prompt = "Name?"
+            validate = "^[A-Za-z_][A-Za-z0-9_]*$"
+            error = "Not an identifier"
+
+            [[prompts]]
+            name = "url"
+            prompt = "URL?"
+            validate = "^https://"
+            "#,
+        )
+        .unwrap();
+        let name = manifest.prompts[0].validator().unwrap().unwrap();
+        assert_eq!(name.check("Gain"), Ok(()));
+        assert_eq!(name.check("2 Gain"), Err("Not an identifier".to_owned()));
+        let url = manifest.prompts[1].validator().unwrap().unwrap();
+        assert_eq!(
+            url.check("example.com"),
+            Err("The answer must match \"^https://\"".to_owned())
+        );
+    }
+
+    #[test]
+    fn fails_on_invalid_regexes() {
+        let manifest = TemplateManifest::parse(
+            r#"
+            [[prompts]]
+            name = "plugin_name"
+            prompt = "Name?"
+            validate = "[unclosed"
+            "#,
+        )
+        .unwrap();
+        assert!(manifest.prompts[0].validator().is_err());
+    }
+
+    #[test]
+    fn renders_templates_into_the_project() {
+        let template = tempdir().unwrap();
+        let root = template.path();
+        create_dir_all(root.join("src")).unwrap();
+        create_dir_all(root.join(".git")).unwrap();
+        write(root.join(MANIFEST_FILENAME), "").unwrap();
+        write(root.join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        write(root.join("notes.md"), "not copied").unwrap();
+        write(
+            root.join("src/{{self.plugin_name}}.rs"),
+            "struct {{self.plugin_name}};",
+        )
+        .unwrap();
+        write(root.join("icon.bin"), [0xff, 0xfe, 0x00]).unwrap();
+
+        let manifest = TemplateManifest {
+            template: TemplateInfo {
+                exclude: vec!["notes.md".to_owned()],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let project = tempdir().unwrap();
+        let variables = [("plugin_name", "Gain".to_owned())];
+        manifest
+            .render_into(root, project.path(), &variables)
+            .unwrap();
+
+        let project = project.path();
+        assert_eq!(
+            read_to_string(project.join("src/Gain.rs")).unwrap(),
+            "struct Gain;"
+        );
+        assert_eq!(read(project.join("icon.bin")).unwrap(), [0xff, 0xfe, 0x00]);
+        assert!(!project.join("notes.md").exists());
+        assert!(!project.join(".git").exists());
+        assert!(!project.join(MANIFEST_FILENAME).exists());
+    }
+}
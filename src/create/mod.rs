@@ -1,19 +1,45 @@
 mod boilerplate;
 mod config;
 mod gen;
+mod manifest;
+mod remote;
+mod template;
 
-use anyhow::Result;
-use boilerplate::{LibConfig, StandaloneConfig, Vst3Config};
-use cliclack::log::{error, info};
+use crate::bundle::build_and_bundle;
+use crate::cargo_toml::{find_workspace_root, CargoToml};
+use crate::cli::{CiProvider, NewArgs};
+use crate::doctor::{preflight_libs, preflight_tools};
+use crate::ids::{plugin_ids, register, IdEntry};
+use crate::info::Sources;
+use crate::npcli_toml::{NpcliToml, PluginInfo};
+use crate::preset::PresetFile;
+use crate::rust_file::RustFile;
+use anyhow::{bail, Context, Result};
+use boilerplate::StandaloneConfig;
+use cargo_metadata::MetadataCommand;
+use cliclack::log::{info, warning};
 use cliclack::{confirm, input};
 use colored::Colorize;
-use config::{collect_export_types, configure_lib};
-use config::{configure_clap_export, configure_vst_export};
-use gen::{cargo_new, write_to_lib, write_to_main, write_to_toml};
-use nih_plug_xtask::{build, bundle};
+use config::{ask_prompts, collect_export_types, configure_extra_plugin, configure_lib};
+use config::{configure_clap_export, configure_test_clap, configure_vst_export};
+use gen::{add_workspace_nih_plug, cargo_new, create_workspace, git_init, workspace_dependency};
+use gen::{allows_vst3, find_exports, find_str_const, render_presets_module, write_plugin_module};
+use gen::{
+    ci_path, project_rust_files, write_license, write_license_headers, write_split_lib,
+    CLAP_TESTS_FEATURE,
+};
+use gen::{write_tests, write_to_lib, write_to_main, write_to_toml};
+use manifest::TemplateManifest;
+use regex::Regex;
 use std::env::current_dir;
 use std::env::set_current_dir;
-use std::fs::remove_dir_all;
+use std::fs::{create_dir_all, remove_dir_all, write};
+use std::path::{Path, PathBuf};
+use std::slice::from_ref;
+use toml_edit::{value, Array, InlineTable};
+
+pub use gen::{create_bench, create_lib_crate, write_ci};
+pub use template::Templates;
 // is a whole enum for this really needed?
 #[derive(Clone, PartialEq, Eq)]
 enum ExportType {
@@ -25,17 +51,37 @@ enum ExportType {
 /// ## Parameters
 /// - `name`: The name of the *project* - that being the directory/crate to be created.
 /// - `defaults`: Setting this to true will skip any user input and just create/compile the plugin.
-/// - `skip_first_build`: Setting this option will skip the first compilation. May be helpful to save some time.
-pub fn create_project(name: Option<String>, defaults: bool, skip_first_build: bool) -> Result<()> {
+/// - `skip_build`: Setting this option will skip the first compilation. May be helpful to save some time.
+/// - `template_dir`: An optional directory of templates that take priority over the built-in ones.
+/// - `template`: An optional template repository that replaces the built-in templates *and* prompts.
+/// - `offline`: Use the cached copy of `template` without fetching it.
+/// - `workspace`: Create a new workspace in this directory, with the project as its first member.
+/// - `ci`: Also generate a CI pipeline for this provider.
+/// - `split`: Split the plugin into modules instead of generating a single `lib.rs`.
+///
+/// If this is run inside an existing workspace, the project is added to it as a member.
+pub fn create_project(args: NewArgs) -> Result<()> {
     // TODO: at this top level, instead of using ? operator, actually write user-facing errors!!
+    let NewArgs {
+        defaults,
+        name,
+        skip_build: skip_first_build,
+        template_dir,
+        template,
+        offline,
+        workspace,
+        ci,
+        split,
+    } = args;
+
+    preflight_tools()?;
 
-    // if the user supplies `--defaults`, we will use these
-    let mut lib_config = LibConfig::default();
-    // vst_data has to be initialized so that we have at least one export by default
-    let mut vst_config = Some(Vst3Config::default());
-    // clap/standalone exports are not defaults, so initialize them to None
-    let mut clap_config = None;
-    let mut standalone_config = None;
+    // resolve templates up front so that a bad --template-dir fails before any prompts
+    let templates = Templates::new(template_dir)?;
+    let template_source = template
+        .as_deref()
+        .map(remote::TemplateSource::parse)
+        .transpose()?;
 
     let project_name: String = if let Some(name) = name {
         name
@@ -53,7 +99,24 @@ pub fn create_project(name: Option<String>, defaults: bool, skip_first_build: bo
     };
 
     let current_dir = current_dir().unwrap();
-    let path = current_dir.join(&project_name);
+    // workspace members go in `plugins/`, everything else goes in the current directory
+    let workspace_root = match &workspace {
+        Some(dir) => {
+            let root = current_dir.join(dir);
+            if root.exists() {
+                bail!(
+                    "The directory \"{}\" already exists. To add a plugin to an existing workspace, run `npcli new` inside it.",
+                    dir.display()
+                );
+            }
+            Some(root)
+        }
+        None => find_workspace_root(&current_dir),
+    };
+    let path = match &workspace_root {
+        Some(root) => root.join("plugins").join(&project_name),
+        None => current_dir.join(&project_name),
+    };
 
     if path.exists() {
         let delete_prompt = format!(
@@ -76,62 +139,557 @@ pub fn create_project(name: Option<String>, defaults: bool, skip_first_build: bo
         }
     }
 
-    // TODO:
-    // there is probably a way to refactor this to save on this indent,
-    // i just didnt figure it out :(
-
-    if !defaults {
-        lib_config = configure_lib()?;
-        let plugin_name = lib_config.plugin_name.clone();
-        // beyond the basic info, we need to know which exports to set up
-        let export_types = collect_export_types();
-        // update vst config
-        vst_config = if export_types.contains(&ExportType::Vst3) {
-            Some(configure_vst_export(&plugin_name)?)
-        } else {
-            // since VST is the default type,
-            // if the user UN-selects VST, we need to consider that
-            None
-        };
-        // handle CLAP configuration/code generation
-        if export_types.contains(&ExportType::Clap) {
-            clap_config = Some(configure_clap_export(&plugin_name)?);
+    if let Some(source) = template_source {
+        if workspace.is_some() {
+            create_workspace(workspace_root.as_ref().unwrap())?;
+        }
+        create_from_template(&source, offline, defaults, &project_name, &path)?;
+        if let Some(root) = &workspace_root {
+            add_to_workspace(root, &path)?;
+        }
+        if let Some(provider) = ci {
+            create_ci(workspace_root.as_deref().unwrap_or(&path), provider)?;
         }
+        return build_project(&path, &project_name, skip_first_build);
+    }
 
-        // finally, standalone setup
-        if export_types.contains(&ExportType::Standalone) {
-            standalone_config = Some(StandaloneConfig {
-                // TODO: don't clone here, this looks stupid
-                plugin_name,
-                project_name: project_name.to_string(),
-            });
+    // if the user supplies `--defaults`, every prompt just uses its default
+    let lib_config = configure_lib(defaults)?;
+    let plugin_name = lib_config.plugin_name.clone();
+    // beyond the basic info, we need to know which exports to set up.
+    // VST3 is the only default export
+    let mut export_types = if defaults {
+        vec![ExportType::Vst3]
+    } else {
+        collect_export_types()
+    };
+    // nih_plug's VST3 bindings are GPLv3, so anything that exports VST3 has to be too
+    if export_types.contains(&ExportType::Vst3) && !allows_vst3(&lib_config.license) {
+        warning(format!(
+            "nih_plug's VST3 bindings are licensed under GPLv3, so a VST3 plugin can't be {}.",
+            lib_config.license
+        ))?;
+        let clap_only = confirm("Export CLAP instead of VST3?")
+            .initial_value(true)
+            .interact()?;
+        if clap_only {
+            export_types.retain(|export_type| *export_type != ExportType::Vst3);
+            if !export_types.contains(&ExportType::Clap) {
+                export_types.push(ExportType::Clap);
+            }
         }
     }
 
+    let vst_config = if export_types.contains(&ExportType::Vst3) {
+        Some(configure_vst_export(&plugin_name, defaults)?)
+    } else {
+        None
+    };
+    // handle CLAP configuration/code generation
+    let clap_config = if export_types.contains(&ExportType::Clap) {
+        Some(configure_clap_export(&plugin_name, defaults)?)
+    } else {
+        None
+    };
+    // finally, standalone setup
+    let standalone_config = if export_types.contains(&ExportType::Standalone) {
+        Some(StandaloneConfig {
+            plugin_name,
+            project_name: project_name.to_string(),
+        })
+    } else {
+        None
+    };
+
+    if !skip_first_build {
+        preflight_libs(standalone_config.is_some())?;
+    }
+
     // now, create/modify files
-    cargo_new(&project_name);
+    if workspace.is_some() {
+        create_workspace(workspace_root.as_ref().unwrap())?;
+        println!("Created a new workspace...");
+    }
+
+    cargo_new(&path, workspace_root.is_some());
     println!("Created a new project...");
 
-    write_to_toml(standalone_config.is_some(), &path)?;
+    write_to_toml(
+        standalone_config.is_some(),
+        workspace_root.is_some(),
+        clap_config.is_some(),
+        &path,
+    )?;
+    if let Some(root) = &workspace_root {
+        add_to_workspace(root, &path)?;
+        println!("Added the project to the workspace...");
+    }
     println!("Updated Cargo.toml...");
 
-    write_to_lib(&path, &lib_config, clap_config, vst_config)?;
-    println!("Updated lib.rs...");
+    // a brand new workspace gets a library crate for DSP code shared between its plugins
+    if let (Some(root), true) = (&workspace_root, workspace.is_some()) {
+        let create_lib = defaults
+            || confirm("Create a shared DSP library crate (`libs/dsp`)?")
+                .initial_value(true)
+                .interact()?;
+        if create_lib {
+            create_lib_crate(root, &templates, "dsp")?;
+            println!("Created the dsp library crate...");
+        }
+    }
 
-    write_to_main(&path, standalone_config)?;
+    // remember the IDs, so that `npcli validate` can check the bundles against them
+    let clap_id = clap_config.as_ref().map(|config| config.clap_id.clone());
+    let vst3_id = vst_config.as_ref().map(|config| config.vst_id.clone());
+    register_ids(plugin_ids(
+        &path,
+        &lib_config.plugin_name,
+        clap_id.as_deref(),
+        vst3_id.as_deref(),
+    ))?;
+    NpcliToml::add_plugin(
+        &path,
+        &lib_config.plugin_name,
+        PluginInfo { clap_id, vst3_id },
+    )?;
+    println!("Created npcli.toml...");
+
+    // the tests load the plugin through CLAP, so a plugin that isn't exported as CLAP still
+    // implements it, just for the tests
+    let clap_export = clap_config.is_some();
+    let lib_clap_config = match clap_config {
+        Some(config) => config,
+        None => configure_test_clap(&project_name, &lib_config.plugin_name)?,
+    };
+    write_tests(
+        &path,
+        &templates,
+        &project_name,
+        &lib_config.plugin_name,
+        clap_export,
+    )?;
+    println!("Created tests...");
+
+    if split {
+        write_split_lib(
+            &path,
+            &templates,
+            &lib_config,
+            Some(lib_clap_config),
+            clap_export,
+            vst_config,
+        )?;
+        println!("Created lib.rs, params.rs, dsp.rs, editor.rs and formats.rs...");
+    } else {
+        write_to_lib(
+            &path,
+            &templates,
+            &lib_config,
+            Some(lib_clap_config),
+            clap_export,
+            vst_config,
+        )?;
+        println!("Updated lib.rs...");
+    }
+
+    write_to_main(&path, &templates, standalone_config)?;
     //.unwrap_or(error("There was an error writing to main.rs")?);
     println!("Created main.rs...");
 
-    if skip_first_build {
+    write_license(&path, &lib_config.license, &lib_config.vendor)?;
+    // everything in the new project was just generated
+    write_license_headers(&project_rust_files(&path)?, &lib_config.license)?;
+    println!("Added the {} license...", lib_config.license);
+
+    if let Some(provider) = ci {
+        create_ci(workspace_root.as_deref().unwrap_or(&path), provider)?;
+    }
+
+    build_project(&path, &project_name, skip_first_build)
+}
+
+/// Adds another plugin to the crate at `project_path`, in its own module, with its own params and IDs.
+/// It gets the same exports as the crate's existing plugins, since one export macro covers all of them.
+pub fn create_plugin(
+    project_path: &Path,
+    templates: &Templates,
+    plugin_name: &str,
+) -> Result<PathBuf> {
+    if !Regex::new("^[A-Za-z_][A-Za-z0-9_]*$")?.is_match(plugin_name) {
+        bail!("The plugin name is used as a Rust type name, so it must be a valid identifier.");
+    }
+    let exports = find_exports(project_path)?;
+    // the vendor info is the crate's, so it's taken from the existing plugin
+    let existing = |name: &str, default: &str| -> Result<String> {
+        Ok(find_str_const(project_path, name)?.unwrap_or_else(|| default.to_owned()))
+    };
+    let mut manifest = CargoToml::open(project_path)?;
+    let package = manifest.table_mut("package")?;
+    let license = package
+        .get("license")
+        .and_then(|license| license.as_str())
+        .map(str::to_owned);
+    let crate_name = package
+        .get("name")
+        .and_then(|name| name.as_str())
+        .context("Cargo.toml has no package name")?
+        .to_owned();
+    // crates that aren't exported as CLAP still implement it for their tests, see `write_to_toml`
+    let clap_tests = manifest
+        .doc
+        .get("features")
+        .and_then(|features| features.get(CLAP_TESTS_FEATURE))
+        .is_some();
+    let lib_config = configure_extra_plugin(
+        plugin_name,
+        existing("VENDOR", "NIH-Plug")?,
+        existing("URL", "")?,
+        existing("EMAIL", "")?,
+        license.clone().unwrap_or_else(|| "Proprietary".to_owned()),
+    )?;
+
+    let clap_export = !exports.clap.is_empty();
+    let clap_config = if clap_export {
+        Some(configure_clap_export(plugin_name, false)?)
+    } else if clap_tests {
+        Some(configure_test_clap(&crate_name, plugin_name)?)
+    } else {
+        None
+    };
+    let vst_config = if exports.vst3.is_empty() {
+        None
+    } else {
+        Some(configure_vst_export(plugin_name, false)?)
+    };
+
+    let clap_id = clap_config
+        .as_ref()
+        .filter(|_| clap_export)
+        .map(|config| config.clap_id.clone());
+    let vst3_id = vst_config.as_ref().map(|config| config.vst_id.clone());
+    let ids = plugin_ids(
+        project_path,
+        plugin_name,
+        clap_id.as_deref(),
+        vst3_id.as_deref(),
+    );
+    let module_path = write_plugin_module(
+        project_path,
+        templates,
+        &lib_config,
+        clap_config,
+        clap_export,
+        vst_config,
+    )?;
+    if let Some(license) = license {
+        write_license_headers(from_ref(&module_path), &license)?;
+    }
+    register_ids(ids)?;
+    NpcliToml::add_plugin(project_path, plugin_name, PluginInfo { clap_id, vst3_id })?;
+    Ok(module_path)
+}
+
+/// Adds factory presets to the crate at `project_path`: the presets module, an `Init` preset with
+/// the params' defaults, and a persisted field in the params struct that remembers the last preset.
+pub fn create_presets(
+    project_path: &Path,
+    templates: &Templates,
+    version: &str,
+) -> Result<PathBuf> {
+    let module_path = project_path.join("src").join("presets.rs");
+    if module_path.exists() {
+        bail!("\"{}\" already exists", module_path.display());
+    }
+    let sources = Sources::load(&project_path.join("src"))?;
+    let plugins = sources.plugins(project_path, version)?;
+    let plugin = match plugins.as_slice() {
+        [plugin] => plugin,
+        [] => bail!("There's no `impl Plugin` in src/"),
+        _ => bail!(
+            "The presets module is written for a single plugin, but this crate has {}",
+            plugins.len()
+        ),
+    };
+    let params_struct = plugin.params_struct.as_deref().with_context(|| {
+        format!(
+            "{} doesn't hold a `#[derive(Params)]` struct",
+            plugin.type_name
+        )
+    })?;
+    let (struct_file, _) = sources
+        .find_struct(params_struct)
+        .with_context(|| format!("There's no struct {} in src/", params_struct))?;
+    let (default_file, _) = sources
+        .find_impl("Default", params_struct)
+        .with_context(|| format!("There's no `impl Default for {}` in src/", params_struct))?;
+    let struct_path = struct_file.path().to_path_buf();
+    let default_path = default_file.path().to_path_buf();
+
+    // everything is done in memory first, so that a failed edit doesn't leave half of it behind
+    let init = PresetFile::from_defaults("Init", version, &plugin.params)?;
+    let mut files = Vec::new();
+    // nih_plug saves `#[persist]` fields with the params, so the preset's name survives a session reload
+    let file = open_once(&mut files, &struct_path)?;
+    file.add_field(
+        params_struct,
+        "/// The factory preset that was loaded last\n#[persist = \"preset\"]\npub preset: Arc<RwLock<String>>",
+    )?;
+    import_sync(file)?;
+    let file = open_once(&mut files, &default_path)?;
+    file.add_field_init(
+        params_struct,
+        "preset: Arc::new(RwLock::new(String::new()))",
+    )?;
+    import_sync(file)?;
+    // `pub`, so that the functions the editor doesn't use yet don't cause warnings
+    open_once(&mut files, &project_path.join("src").join("lib.rs"))?
+        .add_items_after_imports("pub mod presets;")?;
+    let module = render_presets_module(templates, &plugin.type_name)?;
+
+    // the preset goes first, since it fails if it already exists
+    init.write(project_path)?;
+    write(&module_path, module)?;
+    for file in files {
+        file.save()?;
+    }
+
+    let mut manifest = CargoToml::open(project_path)?;
+    let dependencies = manifest.table_mut("dependencies")?;
+    dependencies.entry("serde").or_insert_with(|| {
+        let mut serde = InlineTable::new();
+        serde.insert("version", "1.0".into());
+        serde.insert("features", Array::from_iter(["derive"]).into());
+        value(serde)
+    });
+    dependencies
+        .entry("serde_json")
+        .or_insert_with(|| value("1.0"));
+    manifest.save()?;
+
+    if let Some(license) = CargoToml::open(project_path)?
+        .table_mut("package")?
+        .get("license")
+        .and_then(|license| license.as_str())
+    {
+        write_license_headers(from_ref(&module_path), license)?;
+    }
+    Ok(module_path)
+}
+
+/// The file at `path` from `files`, which it's opened into first if it isn't there yet.
+fn open_once<'a>(files: &'a mut Vec<RustFile>, path: &Path) -> Result<&'a mut RustFile> {
+    let index = match files.iter().position(|file| file.path() == path) {
+        Some(index) => index,
+        None => {
+            files.push(RustFile::open(path)?);
+            files.len() - 1
+        }
+    };
+    Ok(&mut files[index])
+}
+
+/// Makes sure `Arc` and `RwLock` are imported, for the `preset` field.
+fn import_sync(file: &mut RustFile) -> Result<()> {
+    let import = match (file.uses("Arc")?, file.uses("RwLock")?) {
+        (true, true) => return Ok(()),
+        (true, false) => "use std::sync::RwLock;",
+        (false, true) => "use std::sync::Arc;",
+        (false, false) => "use std::sync::{Arc, RwLock};",
+    };
+    file.add_items_after_imports(import)
+}
+
+/// Fetches a template repository, asks its prompts, and renders it into `path`.
+fn create_from_template(
+    source: &remote::TemplateSource,
+    offline: bool,
+    defaults: bool,
+    project_name: &str,
+    path: &Path,
+) -> Result<()> {
+    info(format!("Fetching template from {}...", source.url))?;
+    let root = remote::fetch(source, &remote::cache_root()?, offline)?;
+    let manifest = TemplateManifest::load(&root)?;
+    if let Some(description) = &manifest.template.description {
+        info(description)?;
+    }
+
+    let answers = ask_prompts(&manifest, defaults)?;
+    let mut variables = vec![
+        ("project_name", project_name.to_owned()),
+        ("crate_name", project_name.replace('-', "_")),
+    ];
+    variables.extend(answers.variables());
+
+    create_dir_all(path)?;
+    manifest.render_into(&root, path, &variables)?;
+    // workspace members are already part of the workspace's repo
+    if find_workspace_root(path).is_none() {
+        git_init(path);
+    }
+    println!("Created a new project from the template...");
+    Ok(())
+}
+
+/// Lists the project in `workspace.members`, and makes sure the workspace shares `nih_plug`.
+/// The project also gets every library crate the workspace shares, e.g. `libs/dsp`.
+fn add_to_workspace(root: &Path, path: &Path) -> Result<()> {
+    let mut manifest = CargoToml::open(root)?;
+    let member = path
+        .strip_prefix(root)?
+        .to_string_lossy()
+        .replace('\\', "/");
+    manifest.add_workspace_member(&member)?;
+    add_workspace_nih_plug(&mut manifest)?;
+    manifest.save()?;
+
+    let shared_libs: Vec<String> = manifest
+        .table_mut("workspace.dependencies")?
+        .iter()
+        .filter(|(_, dependency)| dependency.get("path").is_some())
+        .map(|(name, _)| name.to_owned())
+        .collect();
+    let mut project_manifest = CargoToml::open(path)?;
+    let dependencies = project_manifest.table_mut("dependencies")?;
+    for lib in shared_libs {
+        dependencies.insert(&lib, value(workspace_dependency()));
+    }
+    project_manifest.save()
+}
+
+/// Adds the IDs to npcli's registry, which warns if another plugin already uses them.
+fn register_ids(ids: Vec<IdEntry>) -> Result<()> {
+    if let Err(err) = register(ids) {
+        warning(format!("Didn't update the ID registry: {:#}", err))?;
+    }
+    Ok(())
+}
+
+/// Adds a CI pipeline to the repo at `root`, which is the workspace's when there is one.
+/// An existing pipeline is left alone, since the user may have changed it.
+fn create_ci(root: &Path, provider: CiProvider) -> Result<()> {
+    let path = ci_path(root, provider);
+    if path.exists() {
+        warning(format!(
+            "Didn't create a CI pipeline: \"{}\" already exists. Run `npcli add ci --force` to replace it.",
+            path.display()
+        ))?;
+        return Ok(());
+    }
+    match write_ci(root, provider, false) {
+        Ok(path) => println!("Created {}...", path.display()),
+        Err(err) => warning(format!("Didn't create a CI pipeline: {:#}", err))?,
+    }
+    Ok(())
+}
+
+/// Builds and bundles a freshly created project, unless `skip` is set.
+fn build_project(path: &Path, project_name: &str, skip: bool) -> Result<()> {
+    if skip {
         return Ok(());
     }
 
     println!("Beginning build...");
     // finally, build the plugin
     let args = &["--release".to_owned()];
-    set_current_dir(&path)?;
-    build(&[project_name.clone()], args)?;
-    bundle(&path.join("target"), &project_name, args, false)?;
+    set_current_dir(path)?;
+    // for workspace members, the target directory is the workspace's
+    let target_dir = MetadataCommand::new().no_deps().exec()?.target_directory;
+    build_and_bundle(target_dir.as_std_path(), &[project_name.to_owned()], args)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::bundle_built;
+    use crate::render::render;
+    use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+    use std::process::Command;
+    use tempfile::tempdir;
+
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+
+    // a regression test for the built-in `lib.txt`: the default gain plugin, exported as CLAP so
+    // that `npcli render` can load it, has to turn the level down by 6 dB when asked to.
+    // CI runs it with `cargo test -- --ignored`.
+    #[test]
+    #[ignore = "downloads and builds nih_plug, which takes a few minutes"]
+    fn gain_template_renders_at_the_right_level() {
+        let temp = tempdir().unwrap();
+        let path = temp.path().join("gain");
+        let templates = Templates::new(None).unwrap();
+        let lib_config = configure_lib(true).unwrap();
+        let clap_config = configure_clap_export(&lib_config.plugin_name, true).unwrap();
+        cargo_new(&path, false);
+        write_to_toml(false, false, true, &path).unwrap();
+        write_to_lib(
+            &path,
+            &templates,
+            &lib_config,
+            Some(clap_config),
+            true,
+            None,
+        )
+        .unwrap();
+
+        // built in the project's directory, but bundled from here, since the bundler only needs
+        // the target directory
+        let status = Command::new("cargo")
+            .args(["build", "--release"])
+            .current_dir(&path)
+            .status()
+            .unwrap();
+        assert!(status.success());
+        bundle_built(
+            &path.join("target"),
+            &["gain".to_owned()],
+            &["--release".to_owned()],
+        )
+        .unwrap();
+
+        // one second of a 440 Hz sine at -6 dBFS
+        let input = temp.path().join("sine.wav");
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: 48000,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(&input, spec).unwrap();
+        for i in 0..48000 {
+            let sample = 0.5 * (i as f32 * 440.0 * std::f32::consts::TAU / 48000.0).sin();
+            writer.write_sample(sample).unwrap();
+            writer.write_sample(sample).unwrap();
+        }
+        writer.finalize().unwrap();
+
+        let output = temp.path().join("rendered.wav");
+        render(
+            input.clone(),
+            output.clone(),
+            vec!["gain=-6dB".to_owned()],
+            Some(path.join("target").join("bundled").join("gain.clap")),
+            None,
+            512,
+        )
+        .unwrap();
+
+        let read = |path: &Path| -> Vec<f32> {
+            let mut reader = WavReader::open(path).unwrap();
+            // the left channel, without the first half second that the gain smoother needs
+            let samples: Vec<f32> = reader.samples::<f32>().map(Result::unwrap).collect();
+            samples.iter().step_by(2).skip(24000).copied().collect()
+        };
+        let ratio = rms(&read(&output)) / rms(&read(&input));
+        let expected = 10f32.powf(-6.0 / 20.0);
+        assert!(
+            (ratio - expected).abs() < 0.01,
+            "the output is {} dB quieter instead of 6 dB",
+            -20.0 * ratio.log10()
+        );
+    }
+}
@@ -0,0 +1,281 @@
+// This module fetches project templates from git repositories into a local cache.
+
+use crate::git::git;
+use anyhow::{bail, Context, Result};
+use std::env::var_os;
+use std::fs::{create_dir_all, remove_dir_all, rename};
+use std::path::{Path, PathBuf};
+
+/// Where a template lives, parsed from `--template`.
+///
+/// Supported forms:
+/// - `gh:org/repo[@ref][/subdir]` for GitHub
+/// - `gl:org/repo[@ref][/subdir]` for GitLab
+/// - `<url or path>.git[@ref][/subdir]` for anything else, e.g. a local bare repository
+pub struct TemplateSource {
+    pub url: String,
+    pub git_ref: Option<String>,
+    pub subdir: Option<PathBuf>,
+}
+
+impl TemplateSource {
+    pub fn parse(source: &str) -> Result<Self> {
+        let (url, rest) = if let Some(path) = source.strip_prefix("gh:") {
+            let (repo, rest) = split_repo(path)?;
+            (format!("https://github.com/{}.git", repo), rest)
+        } else if let Some(path) = source.strip_prefix("gl:") {
+            let (repo, rest) = split_repo(path)?;
+            (format!("https://gitlab.com/{}.git", repo), rest)
+        } else {
+            // for plain URLs/paths, the repository ends with ".git", if present
+            match find_git_suffix(source) {
+                Some(end) => (source[..end].to_owned(), &source[end..]),
+                None => (source.to_owned(), ""),
+            }
+        };
+
+        // what's left looks like `[@ref][/subdir]`
+        let (git_ref, subdir) = match rest.strip_prefix('@') {
+            Some(rest) => match rest.split_once('/') {
+                Some((git_ref, subdir)) => (Some(git_ref), Some(subdir)),
+                None => (Some(rest), None),
+            },
+            None => (None, rest.strip_prefix('/')),
+        };
+
+        if git_ref.is_some_and(str::is_empty) {
+            bail!("Template source \"{}\" has an empty ref", source);
+        }
+
+        Ok(Self {
+            url,
+            git_ref: git_ref.map(str::to_owned),
+            subdir: subdir.filter(|s| !s.is_empty()).map(PathBuf::from),
+        })
+    }
+
+    /// The directory this template is cached in, under `cache_root`.
+    fn cache_dir(&self, cache_root: &Path) -> PathBuf {
+        // turn the url + ref into something that's safe to use as a directory name
+        let key: String = format!("{}@{}", self.url, self.git_ref.as_deref().unwrap_or("HEAD"))
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '.' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        cache_root.join("templates").join(key)
+    }
+}
+
+/// Splits `org/repo[@ref][/subdir]` into `org/repo` and the remainder.
+fn split_repo(path: &str) -> Result<(&str, &str)> {
+    let Some((org, rest)) = path.split_once('/') else {
+        bail!("Expected a repository like \"org/repo\", got \"{}\"", path);
+    };
+    let end = rest.find(['@', '/']).unwrap_or(rest.len());
+    let repo_len = org.len() + 1 + end;
+    Ok((&path[..repo_len], &path[repo_len..]))
+}
+
+/// Finds the end of a `.git` suffix that's followed by `@`, `/`, or nothing.
+fn find_git_suffix(source: &str) -> Option<usize> {
+    source
+        .match_indices(".git")
+        .map(|(i, _)| i + 4)
+        .find(|&end| matches!(source[end..].chars().next(), None | Some('@') | Some('/')))
+}
+
+/// The root of npcli's cache, following the XDG convention (or `%LOCALAPPDATA%` on Windows).
+pub fn cache_root() -> Result<PathBuf> {
+    let base = if let Some(dir) = var_os("XDG_CACHE_HOME") {
+        PathBuf::from(dir)
+    } else if cfg!(target_os = "windows") {
+        var_os("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .context("LOCALAPPDATA is not set")?
+    } else {
+        var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".cache"))
+            .context("HOME is not set")?
+    };
+    Ok(base.join("npcli"))
+}
+
+/// Clones (or refreshes) the template into the cache at `cache_root` (usually `cache_root()`),
+/// and returns the template's root directory.
+/// With `offline` set, the cache is used as-is and nothing is fetched.
+pub fn fetch(source: &TemplateSource, cache_root: &Path, offline: bool) -> Result<PathBuf> {
+    let dir = source.cache_dir(cache_root);
+
+    if offline {
+        if !dir.is_dir() {
+            bail!(
+                "The template \"{}\" isn't cached yet, so it can't be used offline",
+                source.url
+            );
+        }
+    } else {
+        // templates are small, so a fresh clone is simpler than fetching/resetting.
+        // it's cloned next to the cache, so that a failed clone doesn't cost the cached copy
+        let mut partial = dir.clone().into_os_string();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+        if partial.exists() {
+            remove_dir_all(&partial)?;
+        }
+        create_dir_all(&partial)?;
+        if let Err(err) = clone(source, &partial) {
+            remove_dir_all(&partial)?;
+            return Err(err);
+        }
+        if dir.exists() {
+            remove_dir_all(&dir)?;
+        }
+        rename(&partial, &dir)?;
+    }
+
+    let root = match &source.subdir {
+        Some(subdir) => dir.join(subdir),
+        None => dir,
+    };
+    if !root.is_dir() {
+        bail!(
+            "The template directory \"{}\" does not exist",
+            root.display()
+        );
+    }
+    Ok(root)
+}
+
+/// Clones the template into `dir`, which has to be empty.
+fn clone(source: &TemplateSource, dir: &Path) -> Result<()> {
+    git(dir, &["clone", "--quiet", &source.url, "."])?;
+    if let Some(git_ref) = &source.git_ref {
+        git(dir, &["checkout", "--quiet", git_ref])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create::manifest::TemplateManifest;
+    use std::fs::{read_to_string, write};
+    use tempfile::tempdir;
+
+    #[test]
+    fn parses_sources() {
+        let source = TemplateSource::parse("gh:org/repo").unwrap();
+        assert_eq!(source.url, "https://github.com/org/repo.git");
+        assert_eq!(source.git_ref, None);
+        assert_eq!(source.subdir, None);
+
+        let source = TemplateSource::parse("gl:org/repo@v1.0/plugins/gain").unwrap();
+        assert_eq!(source.url, "https://gitlab.com/org/repo.git");
+        assert_eq!(source.git_ref.as_deref(), Some("v1.0"));
+        assert_eq!(source.subdir, Some(PathBuf::from("plugins/gain")));
+
+        let source = TemplateSource::parse("gh:org/repo/gain").unwrap();
+        assert_eq!(source.git_ref, None);
+        assert_eq!(source.subdir, Some(PathBuf::from("gain")));
+
+        let source = TemplateSource::parse("/tmp/templates.git@main").unwrap();
+        assert_eq!(source.url, "/tmp/templates.git");
+        assert_eq!(source.git_ref.as_deref(), Some("main"));
+
+        // `.git` only ends the repository when it's a whole suffix
+        let source = TemplateSource::parse("https://example.com/a.github/t.git/sub").unwrap();
+        assert_eq!(source.url, "https://example.com/a.github/t.git");
+        assert_eq!(source.subdir, Some(PathBuf::from("sub")));
+
+        assert!(TemplateSource::parse("gh:repo").is_err());
+        assert!(TemplateSource::parse("gh:org/repo@").is_err());
+    }
+
+    fn commit(work: &Path, message: &str) {
+        git(work, &["add", "-A"]).unwrap();
+        git(
+            work,
+            &[
+                "-c",
+                "user.name=npcli",
+                "-c",
+                "user.email=npcli@example.com",
+                "commit",
+                "--quiet",
+                "-m",
+                message,
+            ],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn fetches_and_renders_from_a_bare_repository() {
+        let temp = tempdir().unwrap();
+        let cache = temp.path().join("cache");
+
+        // a template repository, with a `v1` tag and a template in `gain/`
+        let work = temp.path().join("work");
+        create_dir_all(work.join("gain").join("src")).unwrap();
+        git(&work, &["init", "--quiet"]).unwrap();
+        write(
+            work.join("gain").join("npcli-template.toml"),
+            "[[prompts]]\nname = \"vendor\"\nprompt = \"Vendor?\"\ndefault = \"Me\"\n",
+        )
+        .unwrap();
+        write(
+            work.join("gain").join("src").join("lib.rs"),
+            "// {{self.crate_name}} by {{self.vendor}}, v1\n",
+        )
+        .unwrap();
+        commit(&work, "v1");
+        git(&work, &["tag", "v1"]).unwrap();
+        write(
+            work.join("gain").join("src").join("lib.rs"),
+            "// {{self.crate_name}} by {{self.vendor}}, v2\n",
+        )
+        .unwrap();
+        commit(&work, "v2");
+        let bare = temp.path().join("templates.git");
+        git(
+            temp.path(),
+            &["clone", "--quiet", "--bare", "work", "templates.git"],
+        )
+        .unwrap();
+
+        let render = |root: &Path, name: &str| {
+            let manifest = TemplateManifest::load(root).unwrap();
+            let project = temp.path().join(name);
+            let variables = [
+                ("crate_name", "my_gain".to_owned()),
+                ("vendor", "Me".to_owned()),
+            ];
+            manifest.render_into(root, &project, &variables).unwrap();
+            // the manifest itself isn't part of the project
+            assert!(!project.join("npcli-template.toml").exists());
+            read_to_string(project.join("src").join("lib.rs")).unwrap()
+        };
+
+        let latest = TemplateSource::parse(&format!("{}/gain", bare.display())).unwrap();
+        let root = fetch(&latest, &cache, false).unwrap();
+        assert_eq!(render(&root, "latest"), "// my_gain by Me, v2\n");
+
+        let tagged = TemplateSource::parse(&format!("{}@v1/gain", bare.display())).unwrap();
+        let root = fetch(&tagged, &cache, false).unwrap();
+        assert_eq!(render(&root, "tagged"), "// my_gain by Me, v1\n");
+
+        // without the repository, fetching fails, but the cached copy still works offline
+        remove_dir_all(&bare).unwrap();
+        assert!(fetch(&tagged, &cache, false).is_err());
+        let root = fetch(&tagged, &cache, true).unwrap();
+        assert_eq!(render(&root, "offline"), "// my_gain by Me, v1\n");
+
+        let uncached = TemplateSource::parse("gh:org/never-fetched").unwrap();
+        assert!(fetch(&uncached, &cache, true).is_err());
+    }
+}
@@ -0,0 +1,436 @@
+// This module handles templates that are loaded at runtime,
+// so that a team can change the starting code without forking npcli.
+
+use super::boilerplate::{BenchConfig, ClapConfig, ClapTestsConfig, DspConfig, LibConfig};
+use super::boilerplate::{PresetsConfig, StandaloneConfig, TestsConfig, Vst3Config};
+use super::boilerplate::{SplitDspConfig, SplitEditorConfig, SplitLibConfig, SplitParamsConfig};
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::env::{split_paths, var_os};
+use std::ffi::OsString;
+use std::fmt::Display;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// Environment variable holding extra template directories, separated like `PATH`.
+pub const TEMPLATE_PATH_VAR: &str = "NPCLI_TEMPLATE_PATH";
+
+/// Anything that can be rendered from a template file.
+/// The built-in (compiled) template is available through `Display`,
+/// and `variables()` exposes the same fields to runtime-loaded templates.
+pub trait Template: Display + Serialize {
+    /// The name of the template file, e.g. `lib.txt`.
+    const FILENAME: &'static str;
+
+    /// Returns every variable that a template may reference as `{{self.<name>}}`: the struct's
+    /// fields, serialized, so that they're always the ones the built-in template has.
+    fn variables(&self) -> Result<Vec<(String, String)>> {
+        let Value::Object(fields) = serde_json::to_value(self)? else {
+            bail!("The variables of {} aren't a struct", Self::FILENAME);
+        };
+        Ok(fields
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => (name, value),
+                value => (name, value.to_string()),
+            })
+            .collect())
+    }
+}
+
+/// An ordered list of directories to look for templates in.
+/// If none of them contain a given template, the built-in one is used instead.
+pub struct Templates {
+    dirs: Vec<PathBuf>,
+}
+
+impl Templates {
+    /// Builds the search path. A directory passed with `--template-dir` takes priority
+    /// over any directories listed in `NPCLI_TEMPLATE_PATH`.
+    pub fn new(template_dir: Option<PathBuf>) -> Result<Self> {
+        Self::search(template_dir, var_os(TEMPLATE_PATH_VAR))
+    }
+
+    /// Like `new`, with the value of `NPCLI_TEMPLATE_PATH` passed in.
+    fn search(template_dir: Option<PathBuf>, template_path: Option<OsString>) -> Result<Self> {
+        let mut dirs = Vec::new();
+        if let Some(dir) = template_dir {
+            if !dir.is_dir() {
+                bail!(
+                    "The template directory \"{}\" does not exist",
+                    dir.display()
+                );
+            }
+            dirs.push(dir);
+        }
+        if let Some(paths) = template_path {
+            // unlike --template-dir, missing directories here are silently skipped
+            dirs.extend(split_paths(&paths).filter(|dir| dir.is_dir()));
+        }
+        Ok(Self { dirs })
+    }
+
+    /// Returns the path of the first template with the given filename, if any.
+    fn find(&self, filename: &str) -> Option<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(filename))
+            .find(|path| path.is_file())
+    }
+
+    /// Renders `config` with the first matching runtime template,
+    /// falling back to the built-in template.
+    pub fn render<T: Template>(&self, config: &T) -> Result<String> {
+        match self.find(T::FILENAME) {
+            Some(path) => render_file(&path, &config.variables()?),
+            None => Ok(config.to_string()),
+        }
+    }
+}
+
+/// Renders a runtime copy of a built-in template: its `%%` conditions, then its variables.
+fn render_file(path: &Path, variables: &[(String, String)]) -> Result<String> {
+    let contents = read_to_string(path)
+        .with_context(|| format!("Failed to read template \"{}\"", path.display()))?;
+    apply_conditions(&contents, variables)
+        .and_then(|contents| render_str(&contents, variables))
+        .with_context(|| format!("Failed to render template \"{}\"", path.display()))
+}
+
+/// Keeps the lines of `template` whose `%%` conditions hold, and drops the `%%` lines themselves.
+/// Only the subset of the built-in templates' code lines that they use is supported:
+/// `%% if <condition> {`, `%% } else if <condition> {`, `%% } else {` and `%% }`,
+/// see `condition_holds`.
+fn apply_conditions(template: &str, variables: &[(String, String)]) -> Result<String> {
+    // for every open `if`: whether its current branch is kept, and whether any branch was
+    let mut branches: Vec<(bool, bool)> = Vec::new();
+    let mut output = String::with_capacity(template.len());
+    for line in template.split_inclusive('\n') {
+        let Some(code) = line.trim_start().strip_prefix("%%") else {
+            if branches.iter().all(|(kept, _)| *kept) {
+                output.push_str(line);
+            }
+            continue;
+        };
+        let code = code.trim();
+        if let Some(condition) = code.strip_prefix("if ").and_then(|c| c.strip_suffix('{')) {
+            let holds = condition_holds(condition, variables)?;
+            branches.push((holds, holds));
+            continue;
+        }
+        let Some((kept, taken)) = branches.last_mut() else {
+            bail!("\"%% {}\" has no `if`", code);
+        };
+        if code == "}" {
+            branches.pop();
+        } else if code == "} else {" {
+            *kept = !*taken;
+            *taken = true;
+        } else if let Some(condition) = code
+            .strip_prefix("} else if ")
+            .and_then(|c| c.strip_suffix('{'))
+        {
+            *kept = !*taken && condition_holds(condition, variables)?;
+            *taken |= *kept;
+        } else {
+            bail!("Unsupported template line \"%% {}\"", code);
+        }
+    }
+    if !branches.is_empty() {
+        bail!("An `%% if` is never closed");
+    }
+    Ok(output)
+}
+
+/// Evaluates comparisons like `self.midi_config == "None"` (or `!=`),
+/// joined by `&&` and `||`, with `&&` binding tighter.
+fn condition_holds(condition: &str, variables: &[(String, String)]) -> Result<bool> {
+    let mut holds = false;
+    for alternative in condition.split("||") {
+        let mut all = true;
+        for comparison in alternative.split("&&") {
+            let comparison = comparison.trim();
+            let (name, negated, literal) =
+                if let Some((name, literal)) = comparison.split_once("==") {
+                    (name, false, literal)
+                } else if let Some((name, literal)) = comparison.split_once("!=") {
+                    (name, true, literal)
+                } else {
+                    bail!("Unsupported condition \"{}\"", comparison);
+                };
+            let name = name
+                .trim()
+                .strip_prefix("self.")
+                .with_context(|| format!("\"{}\" doesn't compare a variable", comparison))?;
+            let literal = literal
+                .trim()
+                .strip_prefix('"')
+                .and_then(|literal| literal.strip_suffix('"'))
+                .with_context(|| format!("\"{}\" isn't compared to a string", comparison))?;
+            let Some((_, value)) = variables.iter().find(|(var, _)| var == name) else {
+                bail!("Unknown template variable \"self.{}\"", name);
+            };
+            all &= (value == literal) != negated;
+        }
+        holds |= all;
+    }
+    Ok(holds)
+}
+
+/// Replaces every `{{self.<name>}}` (or `{{ self.<name> }}`) in `template` with its value.
+/// Any other `{{` is left alone, since Rust format strings and YAML use them too.
+///
+/// **NOTE**: only variable substitution is supported here. Runtime copies of the built-in
+/// templates also get their `%%` conditions (see `apply_conditions`), but `$$` lines won't work.
+pub fn render_str<K: AsRef<str>>(template: &str, variables: &[(K, String)]) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let placeholder = after.find("}}").and_then(|end| {
+            let name = after[..end].trim().strip_prefix("self.")?;
+            is_identifier(name).then_some((name, end))
+        });
+        let Some((name, end)) = placeholder else {
+            output.push_str("{{");
+            rest = after;
+            continue;
+        };
+
+        let Some((_, value)) = variables.iter().find(|(var, _)| var.as_ref() == name) else {
+            bail!("Unknown template variable \"self.{}\"", name);
+        };
+        output.push_str(value);
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Template for LibConfig {
+    const FILENAME: &'static str = "lib.txt";
+}
+
+impl Template for StandaloneConfig {
+    const FILENAME: &'static str = "main.txt";
+}
+
+impl Template for ClapConfig {
+    const FILENAME: &'static str = "clap.txt";
+}
+
+impl Template for Vst3Config {
+    const FILENAME: &'static str = "vst3.txt";
+}
+
+impl Template for TestsConfig {
+    const FILENAME: &'static str = "tests.txt";
+}
+
+impl Template for ClapTestsConfig {
+    const FILENAME: &'static str = "clap_tests.txt";
+}
+
+impl Template for BenchConfig {
+    const FILENAME: &'static str = "bench.txt";
+}
+
+impl Template for PresetsConfig {
+    const FILENAME: &'static str = "presets.txt";
+}
+
+impl Template for DspConfig {
+    const FILENAME: &'static str = "dsp.txt";
+}
+
+impl Template for SplitLibConfig {
+    const FILENAME: &'static str = "split_lib.txt";
+}
+
+impl Template for SplitParamsConfig {
+    const FILENAME: &'static str = "split_params.txt";
+}
+
+impl Template for SplitDspConfig {
+    const FILENAME: &'static str = "split_dsp.txt";
+}
+
+impl Template for SplitEditorConfig {
+    const FILENAME: &'static str = "split_editor.txt";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::join_paths;
+    use std::fs::write;
+    use tempfile::tempdir;
+
+    fn variables() -> Vec<(&'static str, String)> {
+        vec![("plugin_name", "Gain".to_owned())]
+    }
+
+    #[test]
+    fn replaces_variables() {
+        let rendered = render_str(
+            "struct {{self.plugin_name}}; {{ self.plugin_name }}",
+            &variables(),
+        );
+        assert_eq!(rendered.unwrap(), "struct Gain; Gain");
+    }
+
+    #[test]
+    fn leaves_other_braces_alone() {
+        let template = "format!(\"{{}} {{name}}\"); ${{ matrix.os }} {{self.plugin_name}} {{self.";
+        assert_eq!(
+            render_str(template, &variables()).unwrap(),
+            "format!(\"{{}} {{name}}\"); ${{ matrix.os }} Gain {{self."
+        );
+    }
+
+    #[test]
+    fn fails_on_unknown_variables() {
+        let err = render_str("{{self.plugin_nmae}}", &variables()).unwrap_err();
+        assert!(err.to_string().contains("self.plugin_nmae"));
+    }
+
+    #[test]
+    fn variables_are_the_config_fields() {
+        let config = StandaloneConfig {
+            plugin_name: "Gain".to_owned(),
+            project_name: "gain".to_owned(),
+        };
+        assert_eq!(
+            config.variables().unwrap(),
+            [
+                ("plugin_name".to_owned(), "Gain".to_owned()),
+                ("project_name".to_owned(), "gain".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn searches_directories_in_order() {
+        let config = DspConfig {
+            lib_name: "dsp".to_owned(),
+        };
+        let (first, second, cli) = (tempdir().unwrap(), tempdir().unwrap(), tempdir().unwrap());
+        let template_path = || Some(join_paths([first.path(), second.path()]).unwrap());
+
+        // nothing overrides dsp.txt yet, so the built-in template is used
+        let templates = Templates::search(None, template_path()).unwrap();
+        assert_eq!(templates.render(&config).unwrap(), config.to_string());
+
+        write(second.path().join("dsp.txt"), "second {{self.lib_name}}").unwrap();
+        assert_eq!(templates.render(&config).unwrap(), "second dsp");
+        write(first.path().join("dsp.txt"), "first {{self.lib_name}}").unwrap();
+        assert_eq!(templates.render(&config).unwrap(), "first dsp");
+
+        // --template-dir comes before NPCLI_TEMPLATE_PATH, but only for the templates it has
+        let templates = Templates::search(Some(cli.path().to_path_buf()), template_path()).unwrap();
+        assert_eq!(templates.render(&config).unwrap(), "first dsp");
+        write(cli.path().join("dsp.txt"), "cli {{self.lib_name}}").unwrap();
+        assert_eq!(templates.render(&config).unwrap(), "cli dsp");
+
+        assert!(Templates::search(Some(cli.path().join("missing")), None).is_err());
+    }
+
+    fn midi(config: &str, output: &str) -> Vec<(String, String)> {
+        vec![
+            ("midi_config".to_owned(), config.to_owned()),
+            ("midi_output".to_owned(), output.to_owned()),
+        ]
+    }
+
+    #[test]
+    fn keeps_the_branches_whose_conditions_hold() {
+        let template = "\
+start
+%% if self.midi_config == \"MidiCCs\" {
+ccs
+%% } else if self.midi_config != \"None\" && self.midi_output == \"None\" {
+input only
+%% } else {
+other
+%% if self.midi_output != \"None\" || self.midi_config == \"Basic\" {
+output
+%% }
+%% }
+end
+";
+        let render = |config, output| apply_conditions(template, &midi(config, output)).unwrap();
+        assert_eq!(render("MidiCCs", "Basic"), "start\nccs\nend\n");
+        assert_eq!(render("Basic", "None"), "start\ninput only\nend\n");
+        assert_eq!(render("Basic", "Basic"), "start\nother\noutput\nend\n");
+        assert_eq!(render("None", "None"), "start\nother\nend\n");
+    }
+
+    #[test]
+    fn fails_on_unsupported_code_lines() {
+        let variables = midi("None", "None");
+        for template in [
+            "%% if self.midi_nope == \"None\" {\n%% }\n",
+            "%% if self.midi_config {\n%% }\n",
+            "%% if midi_config == \"None\" {\n%% }\n",
+            "%% for note in notes {\n%% }\n",
+            "%% if self.midi_config == \"None\" {\n",
+            "%% }\n",
+        ] {
+            assert!(
+                apply_conditions(template, &variables).is_err(),
+                "{}",
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn renders_runtime_copies_of_the_lib_templates() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("templates");
+        let templates = Templates::search(Some(dir), None).unwrap();
+        for (midi_config, midi_output) in [
+            ("None", "None"),
+            ("None", "Basic"),
+            ("Basic", "None"),
+            ("MidiCCs", "MidiCCs"),
+        ] {
+            let config = LibConfig {
+                plugin_name: "Gain".to_owned(),
+                vendor: "NIH-Plug".to_owned(),
+                url: String::new(),
+                email: String::new(),
+                license: "GPL-3.0-or-later".to_owned(),
+                midi_config: midi_config.to_owned(),
+                midi_output: midi_output.to_owned(),
+            };
+            let lib = templates.render(&config).unwrap();
+            assert!(!lib.contains("%%"));
+            let uses_context = midi_config != "None" || midi_output != "None";
+            assert_eq!(lib.contains("        context: &mut"), uses_context);
+            assert_eq!(lib.contains("next_event()"), midi_config != "None");
+            assert_eq!(
+                lib.contains("midi_ccs: [0.0; 128]"),
+                midi_config == "MidiCCs"
+            );
+            assert_eq!(
+                lib.contains("playing: false"),
+                midi_config == "None" && midi_output != "None"
+            );
+            assert_eq!(
+                lib.contains("context.send_event(event);"),
+                midi_output != "None"
+            );
+        }
+    }
+}
@@ -0,0 +1,177 @@
+// This module rebuilds, bundles and reinstalls plugins whenever their sources change.
+
+use crate::bundle::{bundle_built, select_packages};
+use crate::install::install;
+use anyhow::{Context, Result};
+use cargo_metadata::diagnostic::DiagnosticLevel;
+use cargo_metadata::{Message, MetadataCommand};
+use cliclack::log::{error, info, step, success};
+use std::env::set_current_dir;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread::{sleep, spawn};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often the sources are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long the sources have to stay unchanged before a rebuild starts,
+/// so that saving several files at once only causes one rebuild.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches every workspace member's `src/` and Cargo.toml, and rebuilds, bundles,
+/// and installs (as symlinks) the given packages whenever something changes. Runs until interrupted.
+pub fn dev(packages: Vec<String>, other_args: Vec<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    set_current_dir(&metadata.workspace_root)?;
+    let packages = select_packages(&metadata, packages)?;
+    let target_dir = metadata.target_directory.as_std_path();
+
+    // shared library crates count too, since the plugins depend on them
+    let watched: Vec<PathBuf> = metadata
+        .workspace_packages()
+        .iter()
+        .filter_map(|package| package.manifest_path.parent())
+        .flat_map(|dir| [dir.join("src").into(), dir.join("Cargo.toml").into()])
+        .collect();
+
+    let mut installed = false;
+    let mut last_change = newest_change(&watched);
+    loop {
+        if build_json(&packages, &other_args)? {
+            let result = bundle_built(target_dir, &packages, &other_args).and_then(|()| {
+                // symlinks point to target/bundled, so installing once is enough
+                if installed {
+                    return Ok(());
+                }
+                install(packages.clone(), true, false)
+            });
+            // like a failed build, this is fixed by the next change, so it doesn't stop watching
+            match result {
+                Ok(()) => installed = true,
+                Err(err) => error(format!("{:#}", err))?,
+            }
+        }
+
+        step("Watching for changes... (press Ctrl+C to stop)")?;
+        last_change = wait_for_change(&watched, last_change);
+    }
+}
+
+/// Runs `cargo build` with JSON messages, printing only diagnostics and a summary.
+/// Returns whether the build succeeded.
+fn build_json(packages: &[String], args: &[String]) -> Result<bool> {
+    info("Building...")?;
+    let start = Instant::now();
+
+    let mut command = Command::new("cargo");
+    command
+        .arg("build")
+        .arg("--message-format=json-diagnostic-rendered-ansi");
+    for package in packages {
+        command.arg("-p").arg(package);
+    }
+    let mut child = command
+        .args(args)
+        .stdout(Stdio::piped())
+        // cargo's progress output is replaced by our own summary
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run cargo")?;
+
+    // read on another thread, since cargo blocks once the pipe is full
+    let mut stderr = child
+        .stderr
+        .take()
+        .context("Failed to read cargo's output")?;
+    let stderr = spawn(move || {
+        let mut output = String::new();
+        let _ = stderr.read_to_string(&mut output);
+        output
+    });
+
+    let stdout = child
+        .stdout
+        .take()
+        .context("Failed to read cargo's output")?;
+    let mut errors = 0;
+    let mut warnings = 0;
+    for message in Message::parse_stream(BufReader::new(stdout)) {
+        let Message::CompilerMessage(message) = message? else {
+            continue;
+        };
+        match message.message.level {
+            DiagnosticLevel::Error | DiagnosticLevel::Ice => errors += 1,
+            DiagnosticLevel::Warning => warnings += 1,
+            _ => continue,
+        }
+        if let Some(rendered) = &message.message.rendered {
+            eprint!("{}", rendered);
+        }
+    }
+    let status = child.wait()?;
+    let stderr = stderr.join().unwrap_or_default();
+
+    let elapsed = start.elapsed().as_secs_f32();
+    if status.success() {
+        success(format!(
+            "Built in {:.1}s ({} warning(s))",
+            elapsed, warnings
+        ))?;
+    } else {
+        // errors like a broken Cargo.toml aren't compiler messages, so they're only on stderr
+        if errors == 0 {
+            eprint!("{}", stderr);
+        }
+        error(format!(
+            "Build failed after {:.1}s ({} error(s), {} warning(s))",
+            elapsed, errors, warnings
+        ))?;
+    }
+    Ok(status.success())
+}
+
+/// Blocks until something in `watched` changes and then settles down,
+/// and returns the time of the newest change.
+fn wait_for_change(watched: &[PathBuf], last_change: SystemTime) -> SystemTime {
+    let mut newest = last_change;
+    while newest <= last_change {
+        sleep(POLL_INTERVAL);
+        newest = newest_change(watched);
+    }
+    // debounce: keep waiting until nothing has changed for a while
+    loop {
+        sleep(DEBOUNCE);
+        let latest = newest_change(watched);
+        if latest == newest {
+            return newest;
+        }
+        newest = latest;
+    }
+}
+
+/// The newest modification time of any file in `paths` (recursively).
+fn newest_change(paths: &[PathBuf]) -> SystemTime {
+    paths
+        .iter()
+        .map(|path| newest_in(path))
+        .max()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+fn newest_in(path: &Path) -> SystemTime {
+    let Ok(metadata) = path.metadata() else {
+        return SystemTime::UNIX_EPOCH;
+    };
+    let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    if !metadata.is_dir() {
+        return modified;
+    }
+    // a directory's own mtime changes when files are added or removed
+    path.read_dir()
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| newest_in(&entry.path()))
+        .fold(modified, SystemTime::max)
+}
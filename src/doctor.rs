@@ -0,0 +1,329 @@
+// This module checks that the user's environment can actually build NIH-Plug plugins.
+
+use crate::cargo_toml::CargoToml;
+use anyhow::{bail, Result};
+use cliclack::log::{error, remark, success, warning};
+use std::env::current_dir;
+use std::fs::read_to_string;
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The result of a single check, with an optional hint on how to fix it.
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    pub fn new(name: &str, status: Status, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_owned(),
+            status,
+            detail: detail.into(),
+            hint: None,
+        }
+    }
+
+    pub fn hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn print(&self) -> Result<()> {
+        let message = format!("{}: {}", self.name, self.detail);
+        match self.status {
+            Status::Pass => success(message)?,
+            Status::Warn => warning(message)?,
+            Status::Fail => error(message)?,
+        }
+        if let Some(hint) = &self.hint {
+            remark(hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// A system library that nih_plug needs on Linux, by pkg-config name and distro package names.
+struct SystemLib {
+    pkg_config: &'static str,
+    apt: &'static str,
+    dnf: &'static str,
+    pacman: &'static str,
+    /// Only needed for standalone builds (audio/MIDI backends)
+    standalone_only: bool,
+}
+
+const fn lib(
+    pkg_config: &'static str,
+    apt: &'static str,
+    dnf: &'static str,
+    pacman: &'static str,
+    standalone_only: bool,
+) -> SystemLib {
+    SystemLib {
+        pkg_config,
+        apt,
+        dnf,
+        pacman,
+        standalone_only,
+    }
+}
+
+// see nih_plug's README and CI workflow for where this list comes from
+#[rustfmt::skip]
+const LINUX_LIBS: &[SystemLib] = &[
+    lib("x11", "libx11-dev", "libX11-devel", "libx11", false),
+    lib("x11-xcb", "libx11-xcb-dev", "libX11-devel", "libx11", false),
+    lib("xcb", "libxcb1-dev", "libxcb-devel", "libxcb", false),
+    lib("xcb-icccm", "libxcb-icccm4-dev", "xcb-util-wm-devel", "xcb-util-wm", false),
+    lib("xcb-dri2", "libxcb-dri2-0-dev", "libxcb-devel", "libxcb", false),
+    lib("xcursor", "libxcursor-dev", "libXcursor-devel", "libxcursor", false),
+    lib("xkbcommon", "libxkbcommon-dev", "libxkbcommon-devel", "libxkbcommon", false),
+    lib("gl", "libgl-dev", "mesa-libGL-devel", "libglvnd", false),
+    lib("alsa", "libasound2-dev", "alsa-lib-devel", "alsa-lib", true),
+    lib("jack", "libjack-dev", "jack-audio-connection-kit-devel", "jack2", true),
+];
+
+/// Runs every check and prints the results.
+pub fn doctor() -> Result<()> {
+    let mut checks = check_tools(Some(&current_dir()?));
+    if cfg!(target_os = "linux") {
+        checks.extend(check_linux_libs(true));
+    }
+    for check in &checks {
+        check.print()?;
+    }
+    summarize(&checks)
+}
+
+/// Fails if any check failed, otherwise prints how many warnings there were.
+pub fn summarize(checks: &[Check]) -> Result<()> {
+    let failures = checks.iter().filter(|c| c.status == Status::Fail).count();
+    let warnings = checks.iter().filter(|c| c.status == Status::Warn).count();
+    if failures > 0 {
+        bail!(
+            "{} check(s) failed and {} check(s) need attention",
+            failures,
+            warnings
+        );
+    }
+    println!("All good! ({} warning(s))", warnings);
+    Ok(())
+}
+
+/// Checks the tools `npcli new` needs before anything is asked, printing only what didn't pass.
+/// Fails if anything required to create a project is missing.
+/// The project doesn't exist yet (and never starts out with SIMD), so there's no nightly check.
+pub fn preflight_tools() -> Result<()> {
+    let checks = check_tools(None);
+    print_problems(&checks)?;
+    if checks.iter().any(|check| check.status == Status::Fail) {
+        bail!("Some required tools are missing. Run `npcli doctor` for details.");
+    }
+    Ok(())
+}
+
+/// Checks the system libraries the new project needs to build, printing only what didn't pass.
+/// Missing libraries only matter for the build, so they never stop project creation.
+pub fn preflight_libs(standalone: bool) -> Result<()> {
+    if cfg!(target_os = "linux") {
+        let mut checks = check_linux_libs(standalone);
+        for check in &mut checks {
+            if check.status == Status::Fail {
+                check.status = Status::Warn;
+            }
+        }
+        print_problems(&checks)?;
+    }
+    Ok(())
+}
+
+/// The Debian/Ubuntu packages for every library nih_plug needs on Linux, for CI pipelines.
+pub fn apt_packages() -> Vec<&'static str> {
+    let mut packages = vec!["pkg-config"];
+    for lib in LINUX_LIBS {
+        if !packages.contains(&lib.apt) {
+            packages.push(lib.apt);
+        }
+    }
+    packages
+}
+
+fn print_problems(checks: &[Check]) -> Result<()> {
+    for check in checks.iter().filter(|check| check.status != Status::Pass) {
+        check.print()?;
+    }
+    Ok(())
+}
+
+/// Checks the tools, and the toolchains that the project in `project_dir` needs, if there is one.
+fn check_tools(project_dir: Option<&Path>) -> Vec<Check> {
+    let mut checks = vec![
+        check_tool(
+            "cargo",
+            &["--version"],
+            "Install Rust with rustup: https://rustup.rs",
+        ),
+        check_tool(
+            "git",
+            &["--version"],
+            "Install git: https://git-scm.com/downloads",
+        ),
+    ];
+    checks.extend(check_rustup(project_dir));
+    checks
+}
+
+/// Runs a program and returns its stdout, or `None` if it couldn't be run or failed.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+}
+
+fn check_tool(program: &str, args: &[&str], hint: &str) -> Check {
+    match run(program, args) {
+        Some(version) => Check::new(program, Status::Pass, version),
+        None => Check::new(program, Status::Fail, "not found").hint(hint),
+    }
+}
+
+/// Checks for the targets universal macOS bundles need, and whether a nightly toolchain exists when the project uses SIMD.
+fn check_rustup(project_dir: Option<&Path>) -> Vec<Check> {
+    let Some(targets) = run("rustup", &["target", "list", "--installed"]) else {
+        return vec![Check::new(
+            "rustup",
+            Status::Warn,
+            "not found, so targets and toolchains can't be checked",
+        )
+        .hint("Install Rust with rustup: https://rustup.rs")];
+    };
+    let targets: Vec<&str> = targets.lines().collect();
+    let mut checks = Vec::new();
+
+    // universal macOS bundles need both architectures
+    if cfg!(target_os = "macos") {
+        for target in ["x86_64-apple-darwin", "aarch64-apple-darwin"] {
+            if !targets.contains(&target) {
+                checks.push(
+                    Check::new(
+                        "rustup targets",
+                        Status::Warn,
+                        format!("{} is missing, so universal bundles can't be built", target),
+                    )
+                    .hint(format!("rustup target add {}", target)),
+                );
+            }
+        }
+    }
+
+    if project_dir.is_some_and(project_uses_simd) {
+        let toolchains = run("rustup", &["toolchain", "list"]).unwrap_or_default();
+        checks.push(if toolchains.lines().any(|t| t.starts_with("nightly")) {
+            Check::new(
+                "nightly toolchain",
+                Status::Pass,
+                "installed (needed for SIMD)",
+            )
+        } else {
+            Check::new(
+                "nightly toolchain",
+                Status::Fail,
+                "nih_plug's `simd` feature requires a nightly toolchain",
+            )
+            .hint("rustup toolchain install nightly")
+        });
+    }
+    checks
+}
+
+/// Whether the project in `dir` enables nih_plug's `simd` feature.
+fn project_uses_simd(dir: &Path) -> bool {
+    let Ok(manifest) = CargoToml::open(dir) else {
+        return false;
+    };
+    let dependencies = [
+        manifest.doc.get("dependencies"),
+        manifest
+            .doc
+            .get("workspace")
+            .and_then(|workspace| workspace.get("dependencies")),
+    ];
+    let uses_simd = dependencies
+        .into_iter()
+        .flatten()
+        .filter_map(|dependencies| dependencies.get("nih_plug")?.get("features")?.as_array())
+        .flatten()
+        .any(|feature| feature.as_str() == Some("simd"));
+    uses_simd
+}
+
+fn check_linux_libs(standalone: bool) -> Vec<Check> {
+    if run("pkg-config", &["--version"]).is_none() {
+        return vec![Check::new(
+            "pkg-config",
+            Status::Warn,
+            "not found, so system libraries can't be checked",
+        )
+        .hint(install_hint(
+            &["pkg-config"],
+            &["pkgconf-pkg-config"],
+            &["pkgconf"],
+        ))];
+    }
+
+    LINUX_LIBS
+        .iter()
+        .filter(|lib| standalone || !lib.standalone_only)
+        .map(|lib| {
+            let name = format!("lib {}", lib.pkg_config);
+            if run("pkg-config", &["--exists", lib.pkg_config]).is_some() {
+                return Check::new(&name, Status::Pass, "found");
+            }
+            let (status, detail) = if lib.standalone_only {
+                (Status::Warn, "not found (needed for standalone builds)")
+            } else {
+                (Status::Fail, "not found")
+            };
+            Check::new(&name, status, detail).hint(install_hint(
+                &[lib.apt],
+                &[lib.dnf],
+                &[lib.pacman],
+            ))
+        })
+        .collect()
+}
+
+/// Builds an install command for the current distro, based on `/etc/os-release`.
+fn install_hint(apt: &[&str], dnf: &[&str], pacman: &[&str]) -> String {
+    let os_release = read_to_string("/etc/os-release").unwrap_or_default();
+    let ids: Vec<&str> = os_release
+        .lines()
+        .filter_map(|line| {
+            line.strip_prefix("ID=")
+                .or_else(|| line.strip_prefix("ID_LIKE="))
+        })
+        .flat_map(|value| value.trim_matches('"').split_whitespace())
+        .collect();
+
+    if ids.iter().any(|id| ["debian", "ubuntu"].contains(id)) {
+        format!("sudo apt install {}", apt.join(" "))
+    } else if ids.iter().any(|id| ["fedora", "rhel"].contains(id)) {
+        format!("sudo dnf install {}", dnf.join(" "))
+    } else if ids.iter().any(|id| ["arch"].contains(id)) {
+        format!("sudo pacman -S {}", pacman.join(" "))
+    } else {
+        format!("Install the development packages for: {}", apt.join(" "))
+    }
+}
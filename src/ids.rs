@@ -0,0 +1,346 @@
+// This module keeps a registry of every plugin ID npcli has generated or seen,
+// so that IDs copied from one project to another get noticed.
+
+use crate::bundle::plugin_packages;
+use crate::info::{inspect, CrateReport};
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use cliclack::log::{step, success, warning};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env::var_os;
+use std::fmt::{self, Display};
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+
+/// The IDs in `templates/prompts/`, which every plugin made with `--defaults` shares.
+const DEFAULT_IDS: &[&str] = &["com.moist-plugins-gmbh.gain", "Exactly16Chars!!"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdKind {
+    Clap,
+    Vst3,
+}
+
+impl Display for IdKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IdKind::Clap => write!(f, "CLAP ID"),
+            IdKind::Vst3 => write!(f, "VST3 class ID"),
+        }
+    }
+}
+
+/// One plugin's CLAP or VST3 ID.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct IdEntry {
+    pub kind: IdKind,
+    pub id: String,
+    pub plugin: String,
+    pub project: PathBuf,
+}
+
+impl IdEntry {
+    fn same_plugin(&self, other: &IdEntry) -> bool {
+        self.kind == other.kind && self.plugin == other.plugin && self.project == other.project
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default, rename = "id")]
+    ids: Vec<IdEntry>,
+}
+
+impl Registry {
+    pub fn load() -> Result<Self> {
+        let path = registry_path()?;
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = read_to_string(&path)?;
+        toml::from_str(&contents).with_context(|| format!("Failed to parse \"{}\"", path.display()))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = registry_path()?;
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        let contents = format!(
+            "# Written by npcli. Every CLAP and VST3 ID it has generated or seen, to catch reused IDs.\n{}",
+            toml::to_string(self)?
+        );
+        write(path, contents)?;
+        Ok(())
+    }
+
+    /// Adds the plugin's ID, replacing the one it had before.
+    pub fn record(&mut self, entry: IdEntry) {
+        self.ids.retain(|existing| !existing.same_plugin(&entry));
+        self.ids.push(entry);
+    }
+
+    /// Every plugin that uses this ID. Projects that have been deleted since don't count.
+    pub fn users(&self, kind: IdKind, id: &str) -> Vec<&IdEntry> {
+        self.ids
+            .iter()
+            .filter(|entry| entry.kind == kind && entry.id == id && entry.project.exists())
+            .collect()
+    }
+}
+
+/// Where the registry lives, following the XDG convention (or `%APPDATA%` on Windows).
+/// Unlike the template cache, it can't be recreated, so it's kept with the user's data.
+fn registry_path() -> Result<PathBuf> {
+    let base = if let Some(dir) = var_os("XDG_DATA_HOME") {
+        PathBuf::from(dir)
+    } else if cfg!(target_os = "windows") {
+        var_os("APPDATA")
+            .map(PathBuf::from)
+            .context("APPDATA is not set")?
+    } else {
+        var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("share"))
+            .context("HOME is not set")?
+    };
+    Ok(base.join("npcli").join("ids.toml"))
+}
+
+/// The IDs of a plugin that's being created.
+pub fn plugin_ids(
+    project: &Path,
+    plugin: &str,
+    clap_id: Option<&str>,
+    vst3_id: Option<&str>,
+) -> Vec<IdEntry> {
+    [(IdKind::Clap, clap_id), (IdKind::Vst3, vst3_id)]
+        .into_iter()
+        .filter_map(|(kind, id)| {
+            Some(IdEntry {
+                kind,
+                id: id?.to_owned(),
+                plugin: plugin.to_owned(),
+                project: project.to_path_buf(),
+            })
+        })
+        .collect()
+}
+
+/// The IDs of every plugin in a crate, as found by `npcli info`.
+/// Only the formats a plugin is exported as count, since the others never reach a host.
+pub fn report_ids(report: &CrateReport) -> Vec<IdEntry> {
+    report
+        .plugins
+        .iter()
+        .flat_map(|plugin| {
+            let exported = |format: &str| plugin.exports.iter().any(|export| export == format);
+            plugin_ids(
+                &report.path,
+                &plugin.type_name,
+                plugin
+                    .clap
+                    .as_ref()
+                    .filter(|_| exported("clap"))
+                    .and_then(|clap| clap.id.as_deref()),
+                plugin
+                    .vst3
+                    .as_ref()
+                    .filter(|_| exported("vst3"))
+                    .and_then(|vst3| vst3.class_id.as_deref()),
+            )
+        })
+        .collect()
+}
+
+/// Warns about IDs that other plugins in the registry already use, or that are npcli's defaults.
+pub fn warn_reused(registry: &Registry, entries: &[IdEntry]) -> Result<()> {
+    for entry in entries {
+        let others: Vec<String> = registry
+            .users(entry.kind, &entry.id)
+            .into_iter()
+            .filter(|other| !other.same_plugin(entry))
+            .map(|other| format!("{} in \"{}\"", other.plugin, other.project.display()))
+            .collect();
+        if !others.is_empty() {
+            warning(format!(
+                "{}'s {} \"{}\" is already used by {}. Hosts can't tell plugins with the same ID apart.",
+                entry.plugin,
+                entry.kind,
+                entry.id,
+                others.join(", ")
+            ))?;
+        } else if DEFAULT_IDS.contains(&entry.id.as_str()) {
+            warning(format!(
+                "{}'s {} \"{}\" is npcli's default, so other plugins are likely to use it too.",
+                entry.plugin, entry.kind, entry.id
+            ))?;
+        }
+    }
+    Ok(())
+}
+
+/// Warns about reused IDs, then adds them to the registry.
+pub fn register(entries: Vec<IdEntry>) -> Result<()> {
+    let mut registry = Registry::load()?;
+    warn_reused(&registry, &entries)?;
+    for entry in entries {
+        registry.record(entry);
+    }
+    registry.save()
+}
+
+/// Scans the plugin crates in `dirs` (or the current directory), adds their IDs to the registry,
+/// and fails if any of them is used by another plugin.
+/// Each directory can be a project, or a directory of projects (e.g. a checkout of every repo).
+pub fn check_ids(dirs: Vec<PathBuf>) -> Result<()> {
+    let dirs = if dirs.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        dirs
+    };
+
+    let mut scanned = Vec::new();
+    for dir in &dirs {
+        for manifest in manifests(dir)? {
+            let metadata = match MetadataCommand::new()
+                .manifest_path(&manifest)
+                .no_deps()
+                .exec()
+            {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    warning(format!("Skipped \"{}\": {}", manifest.display(), err))?;
+                    continue;
+                }
+            };
+            for package in plugin_packages(&metadata) {
+                match inspect(&metadata, package) {
+                    Ok(report) => scanned.extend(report_ids(&report)),
+                    Err(err) => warning(format!("Skipped {}: {:#}", package.name, err))?,
+                }
+            }
+        }
+    }
+    step(format!("Found {} IDs", scanned.len()))?;
+
+    let mut registry = Registry::load()?;
+    for entry in &scanned {
+        registry.record(entry.clone());
+    }
+    registry.save()?;
+
+    // with every scanned ID in the registry, each reused ID only has to be reported once
+    let mut reported = HashSet::new();
+    let mut problems = 0;
+    for entry in &scanned {
+        if !reported.insert((entry.kind, entry.id.clone())) {
+            continue;
+        }
+        let users = registry.users(entry.kind, &entry.id);
+        if users.len() > 1 {
+            let users: Vec<String> = users
+                .iter()
+                .map(|user| format!("  {} in \"{}\"", user.plugin, user.project.display()))
+                .collect();
+            warning(format!(
+                "The {} \"{}\" is used by:\n{}",
+                entry.kind,
+                entry.id,
+                users.join("\n")
+            ))?;
+            problems += 1;
+        } else if DEFAULT_IDS.contains(&entry.id.as_str()) {
+            warning(format!(
+                "{} still has npcli's default {} \"{}\"",
+                entry.plugin, entry.kind, entry.id
+            ))?;
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        bail!(
+            "Found {} shared IDs. Give each plugin its own IDs before it's released, since hosts recognize plugins by them.",
+            problems
+        );
+    }
+    success("Every plugin has its own IDs")?;
+    Ok(())
+}
+
+/// The Cargo.toml in `dir`, or else those of its subdirectories.
+fn manifests(dir: &Path) -> Result<Vec<PathBuf>> {
+    let manifest = dir.join("Cargo.toml");
+    if manifest.is_file() {
+        return Ok(vec![manifest]);
+    }
+    let mut manifests: Vec<PathBuf> = dir
+        .read_dir()
+        .with_context(|| format!("Failed to read \"{}\"", dir.display()))?
+        .flatten()
+        .map(|entry| entry.path().join("Cargo.toml"))
+        .filter(|manifest| manifest.is_file())
+        .collect();
+    if manifests.is_empty() {
+        bail!(
+            "There's no Cargo.toml in \"{}\" or its subdirectories",
+            dir.display()
+        );
+    }
+    manifests.sort();
+    Ok(manifests)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::info::Sources;
+    use std::fs::create_dir_all;
+    use tempfile::tempdir;
+
+    #[test]
+    fn skips_ids_of_formats_that_arent_exported() {
+        let dir = tempdir().unwrap();
+        let src = dir.path().join("src");
+        create_dir_all(&src).unwrap();
+        // a VST3 plugin with the test-only CLAP impl `npcli new` generates
+        write(
+            src.join("lib.rs"),
+            r#"
+            struct Gain;
+            impl Plugin for Gain {}
+
+            #[cfg(feature = "clap-tests")]
+            impl ClapPlugin for Gain {
+                const CLAP_ID: &'static str = "com.moist-plugins-gmbh.gain";
+            }
+
+            impl Vst3Plugin for Gain {
+                const VST3_CLASS_ID: [u8; 16] = *b"GainPluginVst3!!";
+            }
+
+            nih_export_vst3!(Gain);
+            "#,
+        )
+        .unwrap();
+
+        let report = CrateReport {
+            name: "gain".to_owned(),
+            version: "0.1.0".to_owned(),
+            path: dir.path().to_path_buf(),
+            nih_plug_features: Vec::new(),
+            bundles: Vec::new(),
+            plugins: Sources::load(&src)
+                .unwrap()
+                .plugins(dir.path(), "0.1.0")
+                .unwrap(),
+        };
+        let ids: Vec<_> = report_ids(&report)
+            .into_iter()
+            .map(|entry| (entry.kind, entry.id))
+            .collect();
+        assert!(ids == [(IdKind::Vst3, "GainPluginVst3!!".to_owned())]);
+    }
+}
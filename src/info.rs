@@ -0,0 +1,696 @@
+// This module reports what's in existing plugin crates, from their Cargo.toml and source code.
+
+use crate::bundle::select_packages;
+use crate::ids::{report_ids, warn_reused, IdEntry, Registry};
+use crate::install::bundle_name;
+use crate::rust_file::{derives, find_const, find_impl, impls, rust_files, type_name, RustFile};
+use anyhow::{Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand, Package};
+use cliclack::log::{info as log_info, remark, step, warning};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use syn::{Expr, Fields, ImplItem, ItemImpl, ItemStruct, Lit, Member, Meta, Stmt, Type, UnOp};
+
+#[derive(Serialize)]
+pub struct CrateReport {
+    pub name: String,
+    pub version: String,
+    pub path: PathBuf,
+    /// The features enabled on the nih_plug dependency
+    pub nih_plug_features: Vec<String>,
+    /// What `npcli bundle` writes to `target/bundled`
+    pub bundles: Vec<String>,
+    pub plugins: Vec<PluginReport>,
+}
+
+#[derive(Serialize)]
+pub struct PluginReport {
+    pub type_name: String,
+    /// The file with the `impl Plugin`, relative to the crate
+    pub file: PathBuf,
+    pub name: Option<String>,
+    pub vendor: Option<String>,
+    pub url: Option<String>,
+    pub email: Option<String>,
+    pub version: Option<String>,
+    /// The formats the plugin is exported as (`clap` and/or `vst3`)
+    pub exports: Vec<String>,
+    /// Only there if the plugin is exported as CLAP
+    pub clap: Option<ClapReport>,
+    /// Only there if the plugin is exported as VST3
+    pub vst3: Option<Vst3Report>,
+    pub midi_input: String,
+    pub midi_output: String,
+    pub audio_io_layouts: Vec<LayoutReport>,
+    /// The `#[derive(Params)]` struct the plugin holds
+    pub params_struct: Option<String>,
+    pub params: Vec<ParamReport>,
+}
+
+#[derive(Serialize)]
+pub struct ClapReport {
+    pub id: Option<String>,
+    pub description: Option<String>,
+    pub features: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Vst3Report {
+    pub class_id: Option<String>,
+    pub subcategories: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct LayoutReport {
+    pub main_input_channels: u32,
+    pub main_output_channels: u32,
+    pub aux_input_ports: Vec<u32>,
+    pub aux_output_ports: Vec<u32>,
+}
+
+#[derive(Serialize)]
+pub struct ParamReport {
+    /// The ID hosts save the parameter's value under, including any `#[nested]` prefix
+    pub id: String,
+    /// The field, e.g. `gain` or `filter.cutoff` for nested params
+    pub field: String,
+    #[serde(rename = "type")]
+    pub ty: String,
+    /// The default value the param is created with, as written in the source
+    pub default: Option<String>,
+    /// The range the param is created with, if it has one (bool and enum params don't)
+    pub range: Option<String>,
+}
+
+/// Prints what's in the given packages (or every plugin crate), as text or as JSON.
+pub fn info(packages: Vec<String>, json: bool) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let mut reports = Vec::new();
+    for name in select_packages(&metadata, packages)? {
+        let package = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == name)
+            .with_context(|| format!("There's no package named {} in the workspace", name))?;
+        reports.push(inspect(&metadata, package)?);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            print_report(report)?;
+        }
+    }
+    // `npcli new` and `npcli ids check` warn about these IDs from now on.
+    // The report has been printed already, so a registry that can't be updated isn't an error.
+    let ids: Vec<IdEntry> = reports.iter().flat_map(report_ids).collect();
+    if let Err(err) = record_ids(ids, !json) {
+        warning(format!("Didn't update the ID registry: {:#}", err))?;
+    }
+    Ok(())
+}
+
+/// Adds the IDs to the registry, first warning about any that another plugin uses if `warn` is set.
+fn record_ids(ids: Vec<IdEntry>, warn: bool) -> Result<()> {
+    let mut registry = Registry::load()?;
+    if warn {
+        warn_reused(&registry, &ids)?;
+    }
+    for entry in ids {
+        registry.record(entry);
+    }
+    registry.save()
+}
+
+/// Reads a package's plugins from its source code, and the rest from its Cargo.toml.
+pub fn inspect(metadata: &Metadata, package: &Package) -> Result<CrateReport> {
+    let dir = package
+        .manifest_path
+        .parent()
+        .context("The package has no directory")?
+        .as_std_path();
+    let sources = Sources::load(&dir.join("src"))?;
+    let version = package.version.to_string();
+
+    let plugins = sources.plugins(dir, &version)?;
+
+    let nih_plug_features = package
+        .dependencies
+        .iter()
+        .find(|dependency| dependency.name == "nih_plug")
+        .map(|dependency| {
+            let mut features = dependency.features.clone();
+            if dependency.uses_default_features {
+                features.insert(0, "default".to_owned());
+            }
+            features
+        })
+        .unwrap_or_default();
+
+    // these are the names `npcli package` looks for
+    let bundle = bundle_name(metadata, &package.name);
+    let mut bundles = Vec::new();
+    if plugins
+        .iter()
+        .any(|plugin| plugin.exports.iter().any(|format| format == "clap"))
+    {
+        bundles.push(format!("{}.clap", bundle));
+    }
+    if plugins
+        .iter()
+        .any(|plugin| plugin.exports.iter().any(|format| format == "vst3"))
+    {
+        bundles.push(format!("{}.vst3", bundle));
+    }
+    if package
+        .targets
+        .iter()
+        .any(|target| target.kind.iter().any(|kind| kind == "bin"))
+    {
+        bundles.push(bundle);
+    }
+
+    Ok(CrateReport {
+        name: package.name.clone(),
+        version,
+        path: dir.to_path_buf(),
+        nih_plug_features,
+        bundles,
+        plugins,
+    })
+}
+
+fn print_report(report: &CrateReport) -> Result<()> {
+    step(format!(
+        "{} {} ({})",
+        report.name,
+        report.version,
+        report.path.display()
+    ))?;
+    remark(format!(
+        "nih_plug features: {}\nBundles: {}",
+        list(&report.nih_plug_features),
+        list(&report.bundles)
+    ))?;
+
+    for plugin in &report.plugins {
+        log_info(format!(
+            "{} ({}): \"{}\" by {}, version {}",
+            plugin.type_name,
+            plugin.file.display(),
+            plugin.name.as_deref().unwrap_or("?"),
+            plugin.vendor.as_deref().unwrap_or("?"),
+            plugin.version.as_deref().unwrap_or("?")
+        ))?;
+
+        let mut lines = vec![format!("Exports: {}", list(&plugin.exports))];
+        if let Some(clap) = &plugin.clap {
+            lines.push(format!(
+                "CLAP ID: {}, features: {}",
+                clap.id.as_deref().unwrap_or("?"),
+                list(&clap.features)
+            ));
+        }
+        if let Some(vst3) = &plugin.vst3 {
+            lines.push(format!(
+                "VST3 class ID: {}, subcategories: {}",
+                vst3.class_id.as_deref().unwrap_or("?"),
+                list(&vst3.subcategories)
+            ));
+        }
+        lines.push(format!(
+            "MIDI input: {}, output: {}",
+            plugin.midi_input, plugin.midi_output
+        ));
+        let layouts: Vec<String> = plugin
+            .audio_io_layouts
+            .iter()
+            .map(|layout| {
+                let mut text = format!(
+                    "{} in / {} out",
+                    layout.main_input_channels, layout.main_output_channels
+                );
+                if !layout.aux_input_ports.is_empty() || !layout.aux_output_ports.is_empty() {
+                    text.push_str(&format!(
+                        " (aux {:?} in / {:?} out)",
+                        layout.aux_input_ports, layout.aux_output_ports
+                    ));
+                }
+                text
+            })
+            .collect();
+        lines.push(format!("Audio layouts: {}", list(&layouts)));
+        let params: Vec<String> = plugin
+            .params
+            .iter()
+            .map(|param| format!("{} ({})", param.id, param.ty))
+            .collect();
+        lines.push(format!("Params: {}", list(&params)));
+        remark(lines.join("\n"))?;
+    }
+    Ok(())
+}
+
+fn list(items: &[String]) -> String {
+    if items.is_empty() {
+        "none".to_owned()
+    } else {
+        items.join(", ")
+    }
+}
+
+/// A crate's parsed source files.
+pub struct Sources {
+    pub files: Vec<(RustFile, syn::File)>,
+}
+
+impl Sources {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let files = rust_files(dir)?
+            .into_iter()
+            .map(RustFile::open)
+            .collect::<Result<_>>()?;
+        Self::parse(files)
+    }
+
+    pub fn parse(files: Vec<RustFile>) -> Result<Self> {
+        let files = files
+            .into_iter()
+            .map(|file| {
+                let syntax = file.parse()?;
+                Ok((file, syntax))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { files })
+    }
+
+    pub fn find_impl(&self, trait_name: &str, ty: &str) -> Option<(&RustFile, &ItemImpl)> {
+        self.files.iter().find_map(|(file, syntax)| {
+            find_impl(syntax, trait_name, Some(ty)).map(|item| (file, item))
+        })
+    }
+
+    pub fn find_struct(&self, name: &str) -> Option<(&RustFile, &ItemStruct)> {
+        self.files.iter().find_map(|(file, syntax)| {
+            syntax.items.iter().find_map(|item| match item {
+                syn::Item::Struct(item) if item.ident == name => Some((file, item)),
+                _ => None,
+            })
+        })
+    }
+
+    /// Every `impl Plugin` in the crate, with file paths relative to the crate's `dir`.
+    pub fn plugins(&self, dir: &Path, version: &str) -> Result<Vec<PluginReport>> {
+        let mut plugins = Vec::new();
+        for (file, syntax) in &self.files {
+            for item in impls(syntax, "Plugin") {
+                let Some(name) = type_name(&item.self_ty) else {
+                    continue;
+                };
+                let plugin = PluginSource {
+                    sources: self,
+                    type_name: &name,
+                    version,
+                };
+                let mut report = plugin.report()?;
+                report.file = file
+                    .path()
+                    .strip_prefix(dir)
+                    .unwrap_or(file.path())
+                    .to_path_buf();
+                plugins.push(report);
+            }
+        }
+        Ok(plugins)
+    }
+
+    /// Whether `name!(...)` is called with `plugin` anywhere in the crate.
+    fn exports(&self, name: &str, plugin: &str) -> Result<bool> {
+        for (file, _) in &self.files {
+            if let Some(args) = file.macro_args(name)? {
+                if args.iter().any(|arg| arg == plugin) {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    /// Collects the `#[id = "..."]` fields of a `#[derive(Params)]` struct, and those of its `#[nested]` structs.
+    pub fn params(&self, struct_name: &str) -> Vec<ParamReport> {
+        let mut params = Vec::new();
+        self.collect_params(struct_name, "", "", "", &mut params);
+        params
+    }
+
+    fn collect_params(
+        &self,
+        struct_name: &str,
+        id_prefix: &str,
+        id_suffix: &str,
+        field_prefix: &str,
+        params: &mut Vec<ParamReport>,
+    ) {
+        let Some((file, item)) = self.find_struct(struct_name) else {
+            return;
+        };
+        let Fields::Named(fields) = &item.fields else {
+            return;
+        };
+        for field in &fields.named {
+            let Some(ident) = &field.ident else {
+                continue;
+            };
+            let field_name = format!("{}{}", field_prefix, ident);
+            if let Some(id) = field.attrs.iter().find_map(|attr| attr_str(attr, "id")) {
+                params.push(ParamReport {
+                    id: format!("{}{}{}", id_prefix, id, id_suffix),
+                    field: field_name,
+                    ty: file.text(&field.ty).to_owned(),
+                    default: self.param_arg(struct_name, &ident.to_string(), 1),
+                    range: self.param_arg(struct_name, &ident.to_string(), 2),
+                });
+                continue;
+            }
+
+            let Some(nested) = field
+                .attrs
+                .iter()
+                .find(|attr| attr.path().is_ident("nested"))
+            else {
+                continue;
+            };
+            // `#[nested(id_prefix = "foo")]` prefixes the IDs with `foo_`, and
+            // `#[nested(array)]` suffixes them with `_1`, `_2`, ... for each element
+            let mut prefix = id_prefix.to_owned();
+            let mut array = false;
+            if matches!(nested.meta, Meta::List(_)) {
+                let _ = nested.parse_nested_meta(|meta| {
+                    if meta.path.is_ident("id_prefix") {
+                        let value: syn::LitStr = meta.value()?.parse()?;
+                        prefix = format!("{}{}_", id_prefix, value.value());
+                    } else if meta.path.is_ident("array") {
+                        array = true;
+                    } else if meta.input.peek(syn::Token![=]) {
+                        let _: Expr = meta.value()?.parse()?;
+                    }
+                    Ok(())
+                });
+            }
+            let Some(nested_struct) = self.params_type(file.text(&field.ty)) else {
+                continue;
+            };
+            match (&field.ty, array) {
+                (Type::Array(ty), true) => {
+                    let length = match &ty.len {
+                        Expr::Lit(lit) => match &lit.lit {
+                            Lit::Int(int) => int.base10_parse::<usize>().unwrap_or(0),
+                            _ => 0,
+                        },
+                        _ => 0,
+                    };
+                    for i in 1..=length {
+                        self.collect_params(
+                            &nested_struct,
+                            &prefix,
+                            &format!("{}_{}", id_suffix, i),
+                            &format!("{}[{}].", field_name, i - 1),
+                            params,
+                        );
+                    }
+                }
+                _ => self.collect_params(
+                    &nested_struct,
+                    &prefix,
+                    id_suffix,
+                    &format!("{}.", field_name),
+                    params,
+                ),
+            }
+        }
+    }
+
+    /// An argument of the param's constructor in `impl Default for <struct_name>`, with its
+    /// whitespace collapsed. For `FloatParam::new(name, default, range)`, 1 is the default value
+    /// and 2 is the range, e.g. `FloatRange::Linear { min: 0.0, max: 1.0 }`.
+    fn param_arg(&self, struct_name: &str, field: &str, index: usize) -> Option<String> {
+        let (file, item) = self.find_impl("Default", struct_name)?;
+        let body = item.items.iter().find_map(|item| match item {
+            ImplItem::Fn(function) if function.sig.ident == "default" => Some(&function.block),
+            _ => None,
+        })?;
+        let Some(Stmt::Expr(Expr::Struct(init), None)) = body.stmts.last() else {
+            return None;
+        };
+        let mut expr = init.fields.iter().find_map(|init| match &init.member {
+            Member::Named(ident) if ident == field => Some(&init.expr),
+            _ => None,
+        })?;
+        // skip the builder calls, like `.with_unit(" dB")`
+        while let Expr::MethodCall(call) = expr {
+            expr = &call.receiver;
+        }
+        let Expr::Call(call) = expr else {
+            return None;
+        };
+        let arg = call.args.iter().nth(index)?;
+        Some(
+            file.text(arg)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+        )
+    }
+
+    /// Finds the `#[derive(Params)]` struct mentioned in a type, e.g. `GainParams` in `Arc<GainParams>`.
+    fn params_type(&self, ty: &str) -> Option<String> {
+        ty.split(|c: char| !c.is_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .find(|word| {
+                self.find_struct(word)
+                    .is_some_and(|(_, item)| derives(&item.attrs, "Params"))
+            })
+            .map(str::to_owned)
+    }
+}
+
+/// A plugin type and the crate it's in, for looking up its consts.
+struct PluginSource<'a> {
+    sources: &'a Sources,
+    type_name: &'a str,
+    version: &'a str,
+}
+
+impl PluginSource<'_> {
+    fn report(&self) -> Result<PluginReport> {
+        let mut exports = Vec::new();
+        for (format, macro_name) in [("clap", "nih_export_clap"), ("vst3", "nih_export_vst3")] {
+            if self.sources.exports(macro_name, self.type_name)? {
+                exports.push(format.to_owned());
+            }
+        }
+
+        // an impl alone doesn't ship anything, e.g. the test-only `ClapPlugin` of VST3 plugins
+        let exported = |format: &str| exports.iter().any(|export| export == format);
+        let clap = self
+            .sources
+            .find_impl("ClapPlugin", self.type_name)
+            .filter(|_| exported("clap"))
+            .map(|_| ClapReport {
+                id: self.string("ClapPlugin", "CLAP_ID"),
+                description: self.string("ClapPlugin", "CLAP_DESCRIPTION"),
+                features: self.list("ClapPlugin", "CLAP_FEATURES"),
+            });
+        let vst3 = self
+            .sources
+            .find_impl("Vst3Plugin", self.type_name)
+            .filter(|_| exported("vst3"))
+            .map(|_| Vst3Report {
+                class_id: self.string("Vst3Plugin", "VST3_CLASS_ID"),
+                subcategories: self.list("Vst3Plugin", "VST3_SUBCATEGORIES"),
+            });
+
+        // the params are whatever `#[derive(Params)]` struct the plugin holds
+        let params_struct = self
+            .sources
+            .find_struct(self.type_name)
+            .and_then(|(file, item)| {
+                item.fields
+                    .iter()
+                    .find_map(|field| self.sources.params_type(file.text(&field.ty)))
+            });
+        let params = params_struct
+            .as_deref()
+            .map(|name| self.sources.params(name))
+            .unwrap_or_default();
+
+        Ok(PluginReport {
+            type_name: self.type_name.to_owned(),
+            file: PathBuf::new(),
+            name: self.string("Plugin", "NAME"),
+            vendor: self.string("Plugin", "VENDOR"),
+            url: self.string("Plugin", "URL"),
+            email: self.string("Plugin", "EMAIL"),
+            version: self.string("Plugin", "VERSION"),
+            exports,
+            clap,
+            vst3,
+            // nih_plug's defaults, for when the consts aren't set
+            midi_input: self
+                .list("Plugin", "MIDI_INPUT")
+                .pop()
+                .unwrap_or_else(|| "None".to_owned()),
+            midi_output: self
+                .list("Plugin", "MIDI_OUTPUT")
+                .pop()
+                .unwrap_or_else(|| "None".to_owned()),
+            audio_io_layouts: self.layouts(),
+            params_struct,
+            params,
+        })
+    }
+
+    fn find(&self, trait_name: &str, name: &str) -> Option<(&RustFile, &Expr)> {
+        let (file, item) = self.sources.find_impl(trait_name, self.type_name)?;
+        find_const(item, name).map(|expr| (file, expr))
+    }
+
+    /// The value of a string const, or the source of its expression if it isn't a plain string.
+    fn string(&self, trait_name: &str, name: &str) -> Option<String> {
+        let (file, expr) = self.find(trait_name, name)?;
+        self.eval_string(file, expr)
+    }
+
+    fn eval_string(&self, file: &RustFile, expr: &Expr) -> Option<String> {
+        match expr {
+            Expr::Lit(lit) => match &lit.lit {
+                Lit::Str(value) => Some(value.value()),
+                Lit::ByteStr(value) => Some(String::from_utf8_lossy(&value.value()).into_owned()),
+                _ => Some(file.text(expr).to_owned()),
+            },
+            // `*b"Exactly16Chars!!"`
+            Expr::Unary(unary) if matches!(unary.op, UnOp::Deref(_)) => {
+                self.eval_string(file, &unary.expr)
+            }
+            Expr::Macro(mac)
+                if mac.mac.path.is_ident("env")
+                    && mac.mac.tokens.to_string().contains("CARGO_PKG_VERSION") =>
+            {
+                Some(self.version.to_owned())
+            }
+            Expr::Call(call) if matches!(&*call.func, Expr::Path(path) if path.path.is_ident("Some")) => {
+                call.args
+                    .first()
+                    .and_then(|arg| self.eval_string(file, arg))
+            }
+            Expr::Path(path) if path.path.is_ident("None") => None,
+            // e.g. `Self::URL`
+            Expr::Path(path)
+                if path.path.segments.len() == 2 && path.path.segments[0].ident == "Self" =>
+            {
+                let name = path.path.segments[1].ident.to_string();
+                ["Plugin", "ClapPlugin", "Vst3Plugin"]
+                    .iter()
+                    .find_map(|trait_name| self.string(trait_name, &name))
+            }
+            _ => Some(file.text(expr).to_owned()),
+        }
+    }
+
+    /// The variant names in a const like `&[ClapFeature::AudioEffect, ClapFeature::Stereo]`,
+    /// or just the variant for a single one like `MidiConfig::Basic`.
+    fn list(&self, trait_name: &str, name: &str) -> Vec<String> {
+        let Some((file, expr)) = self.find(trait_name, name) else {
+            return Vec::new();
+        };
+        let variant = |expr: &Expr| match expr {
+            Expr::Path(path) => path
+                .path
+                .segments
+                .last()
+                .map_or_else(String::new, |segment| segment.ident.to_string()),
+            _ => file.text(expr).to_owned(),
+        };
+        match array_elements(expr) {
+            Some(elements) => elements.into_iter().map(variant).collect(),
+            None => vec![variant(expr)],
+        }
+    }
+
+    fn layouts(&self) -> Vec<LayoutReport> {
+        let Some((_, expr)) = self.find("Plugin", "AUDIO_IO_LAYOUTS") else {
+            return Vec::new();
+        };
+        array_elements(expr)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|layout| match layout {
+                Expr::Struct(layout) => Some(layout),
+                _ => None,
+            })
+            .map(|layout| {
+                let field = |name: &str| {
+                    layout.fields.iter().find_map(|field| match &field.member {
+                        Member::Named(ident) if ident == name => Some(&field.expr),
+                        _ => None,
+                    })
+                };
+                let ports = |name: &str| {
+                    field(name)
+                        .and_then(array_elements)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(channels)
+                        .collect()
+                };
+                // anything left out comes from `..AudioIOLayout::const_default()`, i.e. nothing
+                LayoutReport {
+                    main_input_channels: field("main_input_channels").map_or(0, channels),
+                    main_output_channels: field("main_output_channels").map_or(0, channels),
+                    aux_input_ports: ports("aux_input_ports"),
+                    aux_output_ports: ports("aux_output_ports"),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The elements of `&[...]` or `[...]`.
+fn array_elements(expr: &Expr) -> Option<Vec<&Expr>> {
+    match expr {
+        Expr::Reference(reference) => array_elements(&reference.expr),
+        Expr::Array(array) => Some(array.elems.iter().collect()),
+        _ => None,
+    }
+}
+
+/// The channel count in `NonZeroU32::new(2)`, `new_nonzero_u32(2)` and the like. `None` is 0.
+fn channels(expr: &Expr) -> u32 {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse().unwrap_or(0),
+            _ => 0,
+        },
+        Expr::Call(call) => call.args.first().map_or(0, channels),
+        Expr::MethodCall(call) => channels(&call.receiver),
+        _ => 0,
+    }
+}
+
+/// The value of an attribute like `#[id = "gain"]`.
+fn attr_str(attr: &syn::Attribute, name: &str) -> Option<String> {
+    let Meta::NameValue(meta) = &attr.meta else {
+        return None;
+    };
+    if !meta.path.is_ident(name) {
+        return None;
+    }
+    match &meta.value {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Str(value) => Some(value.value()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
@@ -0,0 +1,226 @@
+// This module copies (or links) bundled plugins into the plugin folders that hosts scan.
+
+use crate::bundle::select_packages;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand};
+use cliclack::log::{info, success};
+use std::env::{split_paths, var_os};
+use std::fs::{copy, create_dir_all, read_to_string, remove_dir, remove_dir_all, remove_file};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Vst3,
+    Clap,
+}
+
+impl Format {
+    const ALL: [Format; 2] = [Format::Vst3, Format::Clap];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Vst3 => "vst3",
+            Format::Clap => "clap",
+        }
+    }
+
+    /// The format of a bundle, going by its extension.
+    pub fn of(bundle: &Path) -> Option<Self> {
+        let extension = bundle.extension()?;
+        Self::ALL
+            .into_iter()
+            .find(|format| extension == format.extension())
+    }
+
+    /// The variable that overrides this format's plugin folder.
+    fn path_var(self) -> &'static str {
+        match self {
+            Format::Vst3 => "VST3_PATH",
+            Format::Clap => "CLAP_PATH",
+        }
+    }
+
+    /// Where plugins of this format go, following each format's conventions for the host OS.
+    fn install_dir(self, system: bool) -> Result<PathBuf> {
+        // an override always wins, and uses the first entry if it's a list
+        if let Some(paths) = var_os(self.path_var()) {
+            if let Some(dir) = split_paths(&paths).next() {
+                return Ok(dir);
+            }
+        }
+
+        let folder = match self {
+            Format::Vst3 => "VST3",
+            Format::Clap => "CLAP",
+        };
+        let dir = if cfg!(target_os = "windows") {
+            if system {
+                env_dir("COMMONPROGRAMFILES")?.join(folder)
+            } else {
+                env_dir("LOCALAPPDATA")?
+                    .join("Programs")
+                    .join("Common")
+                    .join(folder)
+            }
+        } else if cfg!(target_os = "macos") {
+            let base = if system {
+                PathBuf::from("/")
+            } else {
+                env_dir("HOME")?
+            };
+            base.join("Library")
+                .join("Audio")
+                .join("Plug-Ins")
+                .join(folder)
+        } else if system {
+            PathBuf::from("/usr/lib").join(self.extension())
+        } else {
+            env_dir("HOME")?.join(format!(".{}", self.extension()))
+        };
+        Ok(dir)
+    }
+}
+
+fn env_dir(var: &str) -> Result<PathBuf> {
+    var_os(var)
+        .map(PathBuf::from)
+        .with_context(|| format!("{} is not set", var))
+}
+
+/// A bundle in `target/bundled` and where it's installed to.
+struct Artifact {
+    bundle: PathBuf,
+    destination: PathBuf,
+}
+
+/// Finds the bundles for the given packages (or every plugin crate if none are given).
+fn find_artifacts(packages: Vec<String>, system: bool) -> Result<Vec<Artifact>> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let packages = select_packages(&metadata, packages)?;
+
+    let bundled = metadata.target_directory.as_std_path().join("bundled");
+    let mut artifacts = Vec::new();
+    for package in &packages {
+        let bundle_name = bundle_name(&metadata, package);
+        for format in Format::ALL {
+            let file_name = format!("{}.{}", bundle_name, format.extension());
+            let bundle = bundled.join(&file_name);
+            if bundle.exists() {
+                let destination = format.install_dir(system)?.join(&file_name);
+                artifacts.push(Artifact {
+                    bundle,
+                    destination,
+                });
+            }
+        }
+    }
+
+    if artifacts.is_empty() {
+        bail!(
+            "No bundles were found in \"{}\". Run `npcli bundle` first.",
+            bundled.display()
+        );
+    }
+    Ok(artifacts)
+}
+
+/// The name nih_plug's bundler gives a package's bundles, which can be changed in `bundler.toml`.
+pub fn bundle_name(metadata: &Metadata, package: &str) -> String {
+    let bundler_toml = metadata.workspace_root.as_std_path().join("bundler.toml");
+    read_to_string(bundler_toml)
+        .ok()
+        .and_then(|contents| contents.parse::<toml::Table>().ok())
+        .and_then(|table| table.get(package)?.get("name")?.as_str().map(str::to_owned))
+        .unwrap_or_else(|| package.to_owned())
+}
+
+/// Installs the bundled plugins into the host OS's plugin folders.
+/// With `symlink` set, the installed plugins point to `target/bundled` instead of being copies,
+/// so that rebuilding is enough to update them.
+pub fn install(packages: Vec<String>, symlink: bool, system: bool) -> Result<()> {
+    for artifact in find_artifacts(packages, system)? {
+        if let Some(parent) = artifact.destination.parent() {
+            create_dir_all(parent)?;
+        }
+        remove(&artifact.destination)?;
+        if symlink {
+            link(&artifact.bundle, &artifact.destination)?;
+        } else {
+            copy_all(&artifact.bundle, &artifact.destination)?;
+        }
+        success(format!("Installed {}", artifact.destination.display()))?;
+    }
+    Ok(())
+}
+
+/// Removes the installed plugins again. The bundle names come from the metadata rather than
+/// `target/bundled`, so this works after `cargo clean` too.
+pub fn uninstall(packages: Vec<String>, system: bool) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let mut removed = 0;
+    for package in select_packages(&metadata, packages)? {
+        let bundle_name = bundle_name(&metadata, &package);
+        for format in Format::ALL {
+            let destination =
+                format
+                    .install_dir(system)?
+                    .join(format!("{}.{}", bundle_name, format.extension()));
+            if remove(&destination)? {
+                success(format!("Removed {}", destination.display()))?;
+                removed += 1;
+            }
+        }
+    }
+    if removed == 0 {
+        info("None of the plugins are installed")?;
+    }
+    Ok(())
+}
+
+/// Removes a file, directory, or symlink. Returns whether there was anything to remove.
+fn remove(path: &Path) -> Result<bool> {
+    // symlink_metadata so that a symlink is removed rather than what it points to
+    let Ok(metadata) = path.symlink_metadata() else {
+        return Ok(false);
+    };
+    if metadata.is_dir() {
+        remove_dir_all(path)?;
+    } else if metadata.is_symlink() && cfg!(target_os = "windows") && path.is_dir() {
+        // directory symlinks on Windows have to be removed like directories
+        remove_dir(path)?;
+    } else {
+        remove_file(path)?;
+    }
+    Ok(true)
+}
+
+/// Copies a file, or a directory and everything in it.
+fn copy_all(from: &Path, to: &Path) -> Result<()> {
+    if from.is_dir() {
+        create_dir_all(to)?;
+        for entry in from.read_dir()? {
+            let entry = entry?;
+            copy_all(&entry.path(), &to.join(entry.file_name()))?;
+        }
+    } else {
+        copy(from, to)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn link(from: &Path, to: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(from, to)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn link(from: &Path, to: &Path) -> Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+    if from.is_dir() {
+        symlink_dir(from, to)
+    } else {
+        symlink_file(from, to)
+    }
+    .context("Failed to create a symlink. On Windows, this requires Developer Mode")
+}
@@ -1,50 +1,116 @@
+mod add;
+mod audit;
+mod bench;
+mod bundle;
+mod cargo_toml;
 mod cli;
 // naming is hard :(
 mod create;
+mod dev;
+mod doctor;
+mod git;
+mod ids;
+mod info;
+mod install;
+mod npcli_toml;
+mod package;
+mod params;
+mod preset;
+mod render;
+mod rust_file;
+mod validate;
+mod version;
 
 use anyhow::Result;
 use clap::Parser;
 use cli::*;
 
+use add::{add_bench, add_ci, add_lib, add_plugin, add_presets};
+use audit::audit_licenses;
+use bench::bench;
+use bundle::bundle_packages;
 use create::create_project;
-// use nih_plug_xtask::build;
-// use nih_plug_xtask::bundle;
+use dev::dev;
+use doctor::doctor;
+use ids::check_ids;
+use info::info;
+use install::{install, uninstall};
+use package::package;
+use params::check_params;
+use preset::export_preset;
+use render::render;
+use validate::validate;
+use version::bump;
 
 // TODO:
 // - add more comments - WIP
 // - add documentation - WIP
-// - finish bundle/bundle-universal
+// - finish bundle-universal
 // - finish new() - DONE!
 //      - just have to refactor now
 
 fn main() -> Result<()> {
     let args = Cli::parse();
     match args.command {
-        Commands::New {
-            skip_build,
-            defaults,
-            name,
-        } => create_project(name, defaults, skip_build)?,
-        /*
+        Commands::New(args) => create_project(args)?,
         Commands::Bundle {
             packages,
             other_args,
-        } => {
-            // chdir_workspace_root()?;
-            let cargo_metadata = cargo_metadata::MetadataCommand::new()
-                .manifest_path("./Cargo.toml")
-                .exec()
-                .unwrap(); // TODO
-            let target_dir = cargo_metadata.target_directory.as_std_path();
-
-            build(&packages, &other_args)?;
-
-            bundle(target_dir, &packages[0], &other_args, false)?;
-            for package in packages.into_iter().skip(1) {
-                bundle(target_dir, &package, &other_args, false)?;
-            }
-        }
-        */
+        } => bundle_packages(packages, other_args)?,
+        Commands::Install {
+            packages,
+            symlink,
+            system,
+        } => install(packages, symlink, system)?,
+        Commands::Uninstall { packages, system } => uninstall(packages, system)?,
+        Commands::Dev {
+            packages,
+            other_args,
+        } => dev(packages, other_args)?,
+        Commands::Validate { bundles, external } => validate(bundles, external)?,
+        Commands::Render {
+            input,
+            output,
+            params,
+            plugin,
+            package,
+            block_size,
+        } => render(input, output, params, plugin, package, block_size)?,
+        Commands::Bench {
+            packages,
+            compare,
+            other_args,
+        } => bench(packages, compare, other_args)?,
+        Commands::Package {
+            packages,
+            out_dir,
+            deb,
+            appimage,
+        } => package(packages, out_dir, deb, appimage)?,
+        Commands::Info { packages, json } => info(packages, json)?,
+        Commands::CheckParams { packages, against } => check_params(packages, against)?,
+        Commands::Ids { command } => match command {
+            IdsCommands::Check { dirs } => check_ids(dirs)?,
+        },
+        Commands::Preset { command } => match command {
+            PresetCommands::Export {
+                from_state,
+                name,
+                package,
+            } => export_preset(from_state, name, package)?,
+        },
+        Commands::AuditLicenses { packages } => audit_licenses(packages)?,
+        Commands::Version { command } => match command {
+            VersionCommands::Bump { level, packages } => bump(level, packages)?,
+        },
+        Commands::Doctor => doctor()?,
+        Commands::Add { command } => match command {
+            AddCommands::Lib { name } => add_lib(&name)?,
+            AddCommands::Bench { packages } => add_bench(packages)?,
+            AddCommands::Plugin { name, package } => add_plugin(&name, package)?,
+            AddCommands::Presets { package } => add_presets(package)?,
+            AddCommands::Ci { provider, force } => add_ci(provider, force)?,
+        },
         _ => todo!(),
     };
 
@@ -0,0 +1,89 @@
+// This module reads and writes `npcli.toml`, where npcli keeps what it knows about a plugin crate.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::path::Path;
+
+pub const FILENAME: &str = "npcli.toml";
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct NpcliToml {
+    /// Every plugin in the crate, by type name, e.g. `[plugins.Gain]`.
+    #[serde(default)]
+    pub plugins: BTreeMap<String, PluginInfo>,
+}
+
+/// The IDs a plugin was created with. Hosts use these to recognize a plugin,
+/// so they shouldn't change once the plugin has been released.
+#[derive(Default, Serialize, Deserialize)]
+pub struct PluginInfo {
+    pub clap_id: Option<String>,
+    pub vst3_id: Option<String>,
+}
+
+impl NpcliToml {
+    /// Reads the `npcli.toml` in the given crate directory, if there is one.
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(FILENAME);
+        if !path.is_file() {
+            return Ok(None);
+        }
+        let contents = read_to_string(&path)?;
+        let config = toml::from_str(&contents)
+            .with_context(|| format!("Failed to parse \"{}\"", path.display()))?;
+        Ok(Some(config))
+    }
+
+    /// Records the IDs of a new plugin in the crate's `npcli.toml`, creating it if needed.
+    pub fn add_plugin(dir: &Path, type_name: &str, info: PluginInfo) -> Result<()> {
+        let mut config = Self::load(dir)?.unwrap_or_default();
+        config.plugins.insert(type_name.to_owned(), info);
+        config.save(dir)
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let contents = format!(
+            "# Written by npcli. `npcli validate` checks the bundles against these IDs.\n{}",
+            toml::to_string(self)?
+        );
+        write(dir.join(FILENAME), contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn keeps_every_plugin() {
+        let dir = tempdir().unwrap();
+        let gain = PluginInfo {
+            clap_id: Some("com.example.gain".to_owned()),
+            vst3_id: Some("GainPluginVst3!!".to_owned()),
+        };
+        let reverb = PluginInfo {
+            clap_id: None,
+            vst3_id: Some("ReverbPlugVst3!!".to_owned()),
+        };
+        NpcliToml::add_plugin(dir.path(), "Gain", gain).unwrap();
+        NpcliToml::add_plugin(dir.path(), "Reverb", reverb).unwrap();
+
+        let contents = read_to_string(dir.path().join(FILENAME)).unwrap();
+        assert!(contents.contains("[plugins.Gain]"));
+        assert!(contents.contains("[plugins.Reverb]"));
+        let config = NpcliToml::load(dir.path()).unwrap().unwrap();
+        assert_eq!(
+            config.plugins["Gain"].clap_id.as_deref(),
+            Some("com.example.gain")
+        );
+        assert_eq!(config.plugins["Reverb"].clap_id, None);
+        assert_eq!(
+            config.plugins["Reverb"].vst3_id.as_deref(),
+            Some("ReverbPlugVst3!!")
+        );
+    }
+}
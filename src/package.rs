@@ -0,0 +1,443 @@
+// This module turns bundled plugins into archives (and optionally Linux packages) for releases.
+
+use crate::audit::NOTICES_FILENAME;
+use crate::bundle::select_packages;
+use crate::install::bundle_name;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand, Package};
+use cliclack::log::{success, warning};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::env::consts::{ARCH, OS};
+use std::fs::{copy, create_dir_all, read, remove_dir_all, write, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+const CHECKSUMS_FILENAME: &str = "SHA256SUMS";
+/// The libraries a standalone nih_plug binary links on Linux: X11 and OpenGL for the editor,
+/// ALSA and JACK for audio and MIDI. Newer releases renamed ALSA's package to `libasound2t64`.
+const DEB_DEPENDS: &str = "libx11-6, libx11-xcb1, libxcb1, libxcb-icccm4, libxcb-dri2-0, \
+    libxcursor1, libxkbcommon0, libgl1, libasound2t64 | libasound2, libjack-jackd2-0 | libjack0";
+
+/// What goes into a package's archive.
+struct Contents {
+    name: String,
+    version: String,
+    /// Bundles and the standalone binary, from `target/bundled`
+    artifacts: Vec<PathBuf>,
+    standalone: Option<PathBuf>,
+    /// LICENSE, README, and the like
+    documents: Vec<PathBuf>,
+}
+
+/// Archives the bundled outputs of the given packages (or every plugin crate) into `out_dir`,
+/// as `{plugin}-{version}-{os}-{arch}.zip` (`.tar.gz` on Linux), and writes their SHA256 sums.
+/// On Linux, the standalone binary can also be packaged as a `.deb` and/or an AppImage.
+pub fn package(
+    packages: Vec<String>,
+    out_dir: Option<PathBuf>,
+    deb: bool,
+    appimage: bool,
+) -> Result<()> {
+    if (deb || appimage) && OS != "linux" {
+        bail!("`.deb` packages and AppImages can only be built on Linux");
+    }
+
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let out_dir = out_dir.unwrap_or_else(|| metadata.target_directory.as_std_path().join("dist"));
+    create_dir_all(&out_dir)?;
+
+    let mut outputs = Vec::new();
+    for package in select_packages(&metadata, packages)? {
+        let contents = collect_contents(&metadata, &package)?;
+        outputs.push(archive(&contents, &out_dir)?);
+
+        match (&contents.standalone, deb || appimage) {
+            (Some(binary), _) => {
+                if deb {
+                    outputs.push(build_deb(&metadata, &package, &contents, binary, &out_dir)?);
+                }
+                if appimage {
+                    outputs.push(build_appimage(&contents, binary, &out_dir)?);
+                }
+            }
+            (None, true) => warning(format!(
+                "{} has no standalone binary, so there's no .deb or AppImage for it",
+                package
+            ))?,
+            (None, false) => {}
+        }
+    }
+
+    write_checksums(&outputs, &out_dir)?;
+    for output in &outputs {
+        success(format!("Created {}", output.display()))?;
+    }
+    Ok(())
+}
+
+fn find_package<'a>(metadata: &'a Metadata, name: &str) -> Result<&'a Package> {
+    metadata
+        .workspace_packages()
+        .into_iter()
+        .find(|package| package.name == name)
+        .with_context(|| format!("There's no package named {} in the workspace", name))
+}
+
+fn collect_contents(metadata: &Metadata, package_name: &str) -> Result<Contents> {
+    let package = find_package(metadata, package_name)?;
+    let name = bundle_name(metadata, package_name);
+    let bundled = metadata.target_directory.as_std_path().join("bundled");
+
+    let artifacts: Vec<PathBuf> = [
+        format!("{}.vst3", name),
+        format!("{}.clap", name),
+        name.clone(),
+        format!("{}.exe", name),
+    ]
+    .into_iter()
+    .map(|file_name| bundled.join(file_name))
+    .filter(|path| path.exists())
+    .collect();
+    if artifacts.is_empty() {
+        bail!(
+            "No bundles were found for {} in \"{}\". Run `npcli bundle {} -- --release` first.",
+            package_name,
+            bundled.display(),
+            package_name
+        );
+    }
+    let standalone = artifacts
+        .iter()
+        .find(|path| path.is_file() && path.extension().is_none_or(|ext| ext == "exe"))
+        .cloned();
+
+    // the package's own files win over the workspace's
+    let package_dir = package
+        .manifest_path
+        .parent()
+        .context("The package has no directory")?;
+    let mut documents = Vec::new();
+    for prefix in [
+        "LICENSE",
+        "COPYING",
+        "README",
+        "CHANGELOG",
+        NOTICES_FILENAME,
+    ] {
+        let found = [
+            package_dir.as_std_path(),
+            metadata.workspace_root.as_std_path(),
+        ]
+        .into_iter()
+        .find_map(|dir| find_document(dir, prefix));
+        documents.extend(found);
+    }
+    if find_document(package_dir.as_std_path(), NOTICES_FILENAME).is_none() {
+        warning(format!(
+            "{} has no {}. Run `npcli audit-licenses {}` to include one.",
+            package_name, NOTICES_FILENAME, package_name
+        ))?;
+    }
+
+    Ok(Contents {
+        name,
+        version: package.version.to_string(),
+        artifacts,
+        standalone,
+        documents,
+    })
+}
+
+/// Finds a file like `LICENSE`, `LICENSE.md`, or `LICENSE-MIT` in `dir`.
+fn find_document(dir: &Path, prefix: &str) -> Option<PathBuf> {
+    let mut matches: Vec<PathBuf> = dir
+        .read_dir()
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.to_ascii_uppercase().starts_with(prefix))
+        })
+        .collect();
+    matches.sort();
+    matches.into_iter().next()
+}
+
+/// Writes the archive and returns its path. Everything goes in a `{plugin}-{version}-{os}-{arch}/` folder.
+fn archive(contents: &Contents, out_dir: &Path) -> Result<PathBuf> {
+    let root = format!("{}-{}-{}-{}", contents.name, contents.version, OS, ARCH);
+    let files = contents.artifacts.iter().chain(&contents.documents);
+
+    if OS == "linux" {
+        let path = out_dir.join(format!("{}.tar.gz", root));
+        let encoder = GzEncoder::new(File::create(&path)?, Compression::default());
+        let mut tar = tar::Builder::new(encoder);
+        for file in files {
+            let name = format!("{}/{}", root, file_name(file)?);
+            if file.is_dir() {
+                tar.append_dir_all(name, file)?;
+            } else {
+                tar.append_path_with_name(file, name)?;
+            }
+        }
+        tar.into_inner()?.finish()?;
+        Ok(path)
+    } else {
+        let path = out_dir.join(format!("{}.zip", root));
+        let mut zip = ZipWriter::new(File::create(&path)?);
+        for file in files {
+            add_to_zip(&mut zip, file, &format!("{}/{}", root, file_name(file)?))?;
+        }
+        zip.finish()?;
+        Ok(path)
+    }
+}
+
+fn file_name(path: &Path) -> Result<&str> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("\"{}\" doesn't have a valid file name", path.display()))
+}
+
+/// Adds a file, or a directory and everything in it (e.g. a `.vst3` bundle), to the zip.
+fn add_to_zip(zip: &mut ZipWriter<File>, path: &Path, name: &str) -> Result<()> {
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(permissions(path)?);
+    if path.is_dir() {
+        zip.add_directory(format!("{}/", name), options)?;
+        for entry in path.read_dir()? {
+            let entry = entry?;
+            let entry_name = format!("{}/{}", name, entry.file_name().to_string_lossy());
+            add_to_zip(zip, &entry.path(), &entry_name)?;
+        }
+    } else {
+        zip.start_file(name, options)?;
+        io::copy(&mut File::open(path)?, zip)?;
+    }
+    Ok(())
+}
+
+// macOS binaries inside the bundles have to stay executable
+#[cfg(unix)]
+fn permissions(path: &Path) -> Result<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(path.metadata()?.permissions().mode())
+}
+
+#[cfg(not(unix))]
+fn permissions(path: &Path) -> Result<u32> {
+    Ok(if path.is_dir() { 0o755 } else { 0o644 })
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// Builds a `.deb` that installs the standalone binary to `/usr/bin`.
+fn build_deb(
+    metadata: &Metadata,
+    package_name: &str,
+    contents: &Contents,
+    binary: &Path,
+    out_dir: &Path,
+) -> Result<PathBuf> {
+    let package = find_package(metadata, package_name)?;
+    // Debian package names are lowercase, and `~` sorts pre-releases before the release
+    let deb_name: String = contents
+        .name
+        .to_ascii_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '+' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let version = contents.version.replace('-', "~");
+    let arch = match ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "i386",
+        "arm" => "armhf",
+        other => other,
+    };
+    let control = format!(
+        "Package: {}\nVersion: {}\nArchitecture: {}\nMaintainer: {}\nDepends: {}\nSection: sound\nPriority: optional\nDescription: {}\n",
+        deb_name,
+        version,
+        arch,
+        package.authors.first().map_or("unknown", String::as_str),
+        DEB_DEPENDS,
+        deb_description(package.description.as_deref().unwrap_or(&contents.name)),
+    );
+
+    let mut control_tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    append_bytes(&mut control_tar, "./control", control.as_bytes(), 0o644)?;
+    let control_tar = control_tar.into_inner()?.finish()?;
+
+    let mut data_tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for dir in ["./usr/", "./usr/bin/"] {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        data_tar.append_data(&mut header, dir, io::empty())?;
+    }
+    append_bytes(
+        &mut data_tar,
+        &format!("./usr/bin/{}", deb_name),
+        &read(binary)?,
+        0o755,
+    )?;
+    let data_tar = data_tar.into_inner()?.finish()?;
+
+    let path = out_dir.join(format!("{}_{}_{}.deb", deb_name, version, arch));
+    write_ar(
+        &path,
+        &[
+            ("debian-binary", b"2.0\n".to_vec()),
+            ("control.tar.gz", control_tar),
+            ("data.tar.gz", data_tar),
+        ],
+    )?;
+    Ok(path)
+}
+
+/// Formats a control file's `Description:`: the first line is the summary, the rest continue the
+/// field with a leading space, and blank lines become ` .`.
+fn deb_description(description: &str) -> String {
+    let mut lines = description.trim().lines();
+    let mut field = lines.next().unwrap_or_default().to_owned();
+    for line in lines {
+        match line.trim_end() {
+            "" => field.push_str("\n ."),
+            line => {
+                field.push_str("\n ");
+                field.push_str(line);
+            }
+        }
+    }
+    field
+}
+
+fn append_bytes<W: io::Write>(
+    tar: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+    mode: u32,
+) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(mode);
+    tar.append_data(&mut header, path, data)?;
+    Ok(())
+}
+
+/// Writes an `ar` archive, which is what a `.deb` is on the outside.
+fn write_ar(path: &Path, members: &[(&str, Vec<u8>)]) -> Result<()> {
+    let mut out = b"!<arch>\n".to_vec();
+    for (name, data) in members {
+        // name, mtime, owner, group, mode, size, magic
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name,
+            0,
+            0,
+            0,
+            "100644",
+            data.len()
+        );
+        out.extend_from_slice(header.as_bytes());
+        out.extend_from_slice(data);
+        // members are aligned to two bytes
+        if data.len() % 2 == 1 {
+            out.push(b'\n');
+        }
+    }
+    write(path, out)?;
+    Ok(())
+}
+
+/// Builds an AppImage of the standalone binary with `appimagetool`, which has to be installed.
+fn build_appimage(contents: &Contents, binary: &Path, out_dir: &Path) -> Result<PathBuf> {
+    let name = &contents.name;
+    let app_dir = out_dir.join(format!("{}.AppDir", name));
+    if app_dir.exists() {
+        remove_dir_all(&app_dir)?;
+    }
+    let bin_dir = app_dir.join("usr").join("bin");
+    create_dir_all(&bin_dir)?;
+    copy(binary, bin_dir.join(name))?;
+
+    let app_run = app_dir.join("AppRun");
+    write(
+        &app_run,
+        format!(
+            "#!/bin/sh\nexec \"$(dirname \"$0\")/usr/bin/{}\" \"$@\"\n",
+            name
+        ),
+    )?;
+    make_executable(&app_run)?;
+    write(
+        app_dir.join(format!("{}.desktop", name)),
+        format!(
+            "[Desktop Entry]\nType=Application\nName={0}\nExec={0}\nIcon={0}\nCategories=AudioVideo;Audio;\n",
+            name
+        ),
+    )?;
+    // appimagetool refuses to work without an icon, so start with a placeholder
+    write(
+        app_dir.join(format!("{}.svg", name)),
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"64\" height=\"64\"><rect width=\"64\" height=\"64\" fill=\"#444\"/></svg>\n",
+    )?;
+
+    let path = out_dir.join(format!("{}-{}-{}.AppImage", name, contents.version, ARCH));
+    let status = Command::new("appimagetool")
+        .env("ARCH", ARCH)
+        .arg(&app_dir)
+        .arg(&path)
+        .status();
+    remove_dir_all(&app_dir)?;
+    match status {
+        Ok(status) if status.success() => Ok(path),
+        Ok(_) => bail!("appimagetool failed"),
+        Err(_) => bail!(
+            "appimagetool was not found. Get it from https://github.com/AppImage/appimagetool"
+        ),
+    }
+}
+
+/// Writes `SHA256SUMS` in the format `sha256sum --check` expects.
+fn write_checksums(outputs: &[PathBuf], out_dir: &Path) -> Result<()> {
+    let mut checksums = String::new();
+    for output in outputs {
+        let mut hasher = Sha256::new();
+        io::copy(&mut File::open(output)?, &mut hasher)?;
+        checksums.push_str(&format!(
+            "{:x}  {}\n",
+            hasher.finalize(),
+            file_name(output)?
+        ));
+    }
+    write(out_dir.join(CHECKSUMS_FILENAME), checksums)?;
+    Ok(())
+}
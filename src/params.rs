@@ -0,0 +1,158 @@
+// This module checks that parameter IDs stay stable between versions, since saved sessions depend on them.
+
+use crate::bundle::select_packages;
+use crate::git::git;
+use crate::info::{ParamReport, PluginReport, Sources};
+use crate::rust_file::RustFile;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use cliclack::log::{info, remark, step, success, warning};
+use std::path::Path;
+
+/// Compares the params of the given packages (or every plugin crate) with those at the git ref
+/// `against`, e.g. the last release's tag. Fails if an ID was removed or renamed, or if a param's
+/// type or range changed, since sessions and automation saved with the old version would break.
+pub fn check_params(packages: Vec<String>, against: String) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let root = metadata.workspace_root.as_std_path();
+    let commit = format!("{}^{{commit}}", against);
+    if git(root, &["rev-parse", "--verify", "--quiet", &commit]).is_err() {
+        bail!(
+            "\"{}\" isn't a commit, branch or tag in this repository",
+            against
+        );
+    }
+
+    let mut problems = 0;
+    for name in select_packages(&metadata, packages)? {
+        let package = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == name)
+            .with_context(|| format!("There's no package named {} in the workspace", name))?;
+        let dir = package
+            .manifest_path
+            .parent()
+            .context("The package has no directory")?
+            .as_std_path();
+        step(format!("{} (against {})", name, against))?;
+
+        let version = package.version.to_string();
+        let current = Sources::load(&dir.join("src"))?.plugins(dir, &version)?;
+        let Some(old) = sources_at(dir, &against)? else {
+            remark(format!(
+                "{} didn't exist at {}, so there's nothing to compare",
+                name, against
+            ))?;
+            continue;
+        };
+        let old = old.plugins(dir, &version)?;
+
+        for old_plugin in &old {
+            match current
+                .iter()
+                .find(|plugin| plugin.type_name == old_plugin.type_name)
+            {
+                Some(plugin) => problems += compare(old_plugin, plugin)?,
+                None => warning(format!(
+                    "{} was removed (or renamed), so its params weren't checked",
+                    old_plugin.type_name
+                ))?,
+            }
+        }
+    }
+
+    if problems > 0 {
+        bail!(
+            "Found {} param changes that break saved sessions. Keep the old IDs and ranges, or migrate old state in `Plugin::filter_state()`, which can rename IDs and convert values before a saved `PluginState` is loaded.",
+            problems
+        );
+    }
+    success("Every param ID is still there, with the same type and range")?;
+    Ok(())
+}
+
+/// Reads the crate's `src/` at `git_ref`, or returns `None` if it didn't exist yet.
+fn sources_at(dir: &Path, git_ref: &str) -> Result<Option<Sources>> {
+    let paths = git(dir, &["ls-tree", "-r", "--name-only", git_ref, "--", "src"])?;
+    let mut files = Vec::new();
+    // ls-tree lists the paths relative to `dir`, and `./` makes `git show` read them that way too
+    for path in paths.lines().filter(|path| path.ends_with(".rs")) {
+        let source = git(dir, &["show", &format!("{}:./{}", git_ref, path)])?;
+        files.push(RustFile::from_source(dir.join(path), source)?);
+    }
+    if files.is_empty() {
+        return Ok(None);
+    }
+    Sources::parse(files).map(Some)
+}
+
+/// Reports how a plugin's params changed, and returns the number of breaking changes.
+fn compare(old: &PluginReport, new: &PluginReport) -> Result<usize> {
+    let mut problems = 0;
+    for old_param in &old.params {
+        let Some(param) = new.params.iter().find(|param| param.id == old_param.id) else {
+            // a field that's still there under another ID was most likely renamed
+            match new
+                .params
+                .iter()
+                .find(|param| param.field == old_param.field)
+            {
+                Some(param) => warning(format!(
+                    "{}: the ID of `{}` changed from \"{}\" to \"{}\"",
+                    new.type_name, param.field, old_param.id, param.id
+                ))?,
+                None => warning(format!(
+                    "{}: \"{}\" (`{}`) was removed",
+                    new.type_name, old_param.id, old_param.field
+                ))?,
+            }
+            problems += 1;
+            continue;
+        };
+
+        if normalized(&param.ty) != normalized(&old_param.ty) {
+            warning(format!(
+                "{}: \"{}\" changed from {} to {}",
+                new.type_name, param.id, old_param.ty, param.ty
+            ))?;
+            problems += 1;
+        }
+        if let (Some(old_range), Some(range)) = (&old_param.range, &param.range) {
+            if normalized(range) != normalized(old_range) {
+                warning(format!(
+                    "{}: the range of \"{}\" changed from `{}` to `{}`. Hosts store automation as normalized values, so existing automation would now mean something else.",
+                    new.type_name, param.id, old_range, range
+                ))?;
+                problems += 1;
+            }
+        }
+    }
+
+    let added: Vec<&ParamReport> = new
+        .params
+        .iter()
+        .filter(|param| {
+            !old.params
+                .iter()
+                .any(|old_param| old_param.id == param.id || old_param.field == param.field)
+        })
+        .collect();
+    if !added.is_empty() {
+        let added: Vec<&str> = added.iter().map(|param| param.id.as_str()).collect();
+        info(format!(
+            "{}: new params {}",
+            new.type_name,
+            added.join(", ")
+        ))?;
+    }
+    Ok(problems)
+}
+
+/// Source text without whitespace or trailing commas, so reformatting doesn't count as a change.
+fn normalized(text: &str) -> String {
+    let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    text.replace(",}", "}")
+        .replace(",)", ")")
+        .replace(",]", "]")
+}
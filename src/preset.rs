@@ -0,0 +1,197 @@
+// This module turns plugin states into factory preset files, for the module `npcli add presets` generates.
+
+use crate::bundle::current_plugin_package;
+use crate::info::ParamReport;
+use crate::rust_file::RustFile;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use cliclack::log::remark;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+use syn::{Expr, Lit, UnOp};
+
+/// A factory preset: nih_plug's `PluginState`, plus a name for the preset menu.
+#[derive(Serialize, Deserialize)]
+pub struct PresetFile {
+    #[serde(default)]
+    pub name: String,
+    pub version: String,
+    pub params: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl PresetFile {
+    /// A preset with the params' default values, as far as they can be worked out from the source.
+    pub fn from_defaults(name: &str, version: &str, params: &[ParamReport]) -> Result<Self> {
+        let mut values = BTreeMap::new();
+        for param in params {
+            match param
+                .default
+                .as_deref()
+                .and_then(|default| default_value(&param.ty, default))
+            {
+                Some(value) => {
+                    values.insert(param.id.clone(), value);
+                }
+                None => remark(format!(
+                    "Left \"{}\" out of the {} preset, since its default isn't a plain value",
+                    param.id, name
+                ))?,
+            }
+        }
+        Ok(Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            params: values,
+            fields: BTreeMap::new(),
+        })
+    }
+
+    /// Writes the preset to `presets/<name>.json` and returns its file name.
+    pub fn write(&self, project_path: &Path) -> Result<String> {
+        let file_name = format!("{}.json", slug(&self.name));
+        let dir = project_path.join("presets");
+        let path = dir.join(&file_name);
+        if path.exists() {
+            bail!("\"{}\" already exists", path.display());
+        }
+        create_dir_all(&dir)?;
+        write(&path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(file_name)
+    }
+}
+
+/// Turns a saved state (e.g. from the plugin's tests, or a CLAP host's state file) into a factory
+/// preset, and adds it to the `FACTORY_PRESETS` of the crate's `src/presets.rs`.
+pub fn export_preset(
+    from_state: PathBuf,
+    name: Option<String>,
+    package: Option<String>,
+) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let package = current_plugin_package(&metadata, package)?;
+    let dir = package
+        .manifest_path
+        .parent()
+        .context("The package has no directory")?
+        .as_std_path();
+    let presets_path = dir.join("src").join("presets.rs");
+    if !presets_path.is_file() {
+        bail!(
+            "{} has no src/presets.rs. Run `npcli add presets` first.",
+            package.name
+        );
+    }
+
+    let state = read_to_string(&from_state)
+        .with_context(|| format!("Failed to read \"{}\"", from_state.display()))?;
+    let mut preset: PresetFile = serde_json::from_str(&state).with_context(|| {
+        format!(
+            "\"{}\" isn't a plugin state. nih_plug saves states as JSON with `version`, `params` and `fields`.",
+            from_state.display()
+        )
+    })?;
+    preset.name = match name {
+        Some(name) => name,
+        None => from_state
+            .file_stem()
+            .context("The state file has no name")?
+            .to_string_lossy()
+            .into_owned(),
+    };
+    // fields are things like the editor's size and the last loaded preset, which a preset shouldn't change
+    if !preset.fields.is_empty() {
+        let fields: Vec<&str> = preset.fields.keys().map(String::as_str).collect();
+        remark(format!(
+            "Left out the persisted fields {}, since loading a preset shouldn't change them",
+            fields.join(", ")
+        ))?;
+        preset.fields.clear();
+    }
+
+    let file_name = preset.write(dir)?;
+    let mut presets = RustFile::open(&presets_path)?;
+    presets.add_array_element(
+        "FACTORY_PRESETS",
+        &format!("include_str!(\"../presets/{}\")", file_name),
+    )?;
+    presets.save()?;
+    println!("Created presets/{}...", file_name);
+    println!("Added it to FACTORY_PRESETS in src/presets.rs...");
+    Ok(())
+}
+
+/// `My Preset #2` -> `my-preset-2`
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() {
+        "preset".to_owned()
+    } else {
+        slug
+    }
+}
+
+/// The value nih_plug saves for a param of type `ty` that's set to `default` (a Rust expression).
+/// Only literals and `util::db_to_gain()` are evaluated, since anything else needs the plugin's code.
+fn default_value(ty: &str, default: &str) -> Option<Value> {
+    let expr: Expr = syn::parse_str(default).ok()?;
+    match ty {
+        "FloatParam" => {
+            serde_json::Number::from_f64(number(&expr)? as f32 as f64).map(Value::Number)
+        }
+        "IntParam" => {
+            let value = number(&expr)?;
+            (value.fract() == 0.0).then(|| Value::from(value as i32))
+        }
+        "BoolParam" => match expr {
+            Expr::Lit(lit) => match lit.lit {
+                Lit::Bool(value) => Some(Value::Bool(value.value)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn number(expr: &Expr) -> Option<f64> {
+    match expr {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Float(value) => value.base10_parse().ok(),
+            Lit::Int(value) => value.base10_parse().ok(),
+            _ => None,
+        },
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => number(&unary.expr).map(|n| -n),
+        Expr::Paren(paren) => number(&paren.expr),
+        Expr::Call(call) if call.args.len() == 1 => {
+            let Expr::Path(function) = &*call.func else {
+                return None;
+            };
+            let name = function.path.segments.last()?.ident.to_string();
+            let arg = number(call.args.first()?)?;
+            match name.as_str() {
+                "db_to_gain" => Some(10f64.powf(arg / 20.0)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
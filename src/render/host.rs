@@ -0,0 +1,337 @@
+// A minimal CLAP host: just enough to load a plugin, set parameters, and run `process` offline.
+
+use anyhow::{bail, Context, Result};
+use clap_sys::audio_buffer::clap_audio_buffer;
+use clap_sys::entry::clap_plugin_entry;
+use clap_sys::events::{
+    clap_event_header, clap_event_param_value, clap_input_events, clap_output_events,
+    CLAP_CORE_EVENT_SPACE_ID, CLAP_EVENT_PARAM_VALUE,
+};
+use clap_sys::ext::audio_ports::{
+    clap_audio_port_info, clap_plugin_audio_ports, CLAP_EXT_AUDIO_PORTS,
+};
+use clap_sys::ext::params::{clap_param_info, clap_plugin_params, CLAP_EXT_PARAMS};
+use clap_sys::factory::plugin_factory::{clap_plugin_factory, CLAP_PLUGIN_FACTORY_ID};
+use clap_sys::host::clap_host;
+use clap_sys::plugin::clap_plugin;
+use clap_sys::process::{clap_process, CLAP_PROCESS_ERROR};
+use clap_sys::version::CLAP_VERSION;
+use libloading::Library;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+use std::ptr::{null, null_mut};
+
+/// A plugin instance, together with the library it was loaded from.
+/// Everything is torn down again (in the right order) when this is dropped.
+pub struct Plugin {
+    plugin: *const clap_plugin,
+    entry: *const clap_plugin_entry,
+    // the plugin keeps a pointer to the host, so it has to live as long as the plugin does
+    _host: Box<clap_host>,
+    _library: Library,
+    activated: bool,
+}
+
+/// A parameter the plugin exposes.
+pub struct Param {
+    pub id: u32,
+    pub name: String,
+}
+
+impl Plugin {
+    /// Loads the first plugin in a CLAP binary and initializes it.
+    pub fn load(binary: &Path) -> Result<Self> {
+        let path = CString::new(binary.to_string_lossy().as_bytes())?;
+        // SAFETY: loading a plugin runs its code. That's the point, and the same as any host does
+        unsafe {
+            let library = Library::new(binary)
+                .with_context(|| format!("Failed to load \"{}\"", binary.display()))?;
+            let entry: *const clap_plugin_entry = *library
+                .get::<*const clap_plugin_entry>(b"clap_entry\0")
+                .context("The binary doesn't export `clap_entry`")?;
+            let entry_ref = &*entry;
+            if !(entry_ref.init.context("clap_entry has no init()")?)(path.as_ptr()) {
+                bail!("The plugin's entry point failed to initialize");
+            }
+
+            let factory = (entry_ref
+                .get_factory
+                .context("clap_entry has no get_factory()")?)(
+                CLAP_PLUGIN_FACTORY_ID.as_ptr()
+            ) as *const clap_plugin_factory;
+            if factory.is_null() {
+                bail!("The plugin has no plugin factory");
+            }
+            let factory_ref = &*factory;
+            let count = (factory_ref
+                .get_plugin_count
+                .context("Incomplete plugin factory")?)(factory);
+            if count == 0 {
+                bail!("The binary doesn't contain any plugins");
+            }
+            let descriptor = (factory_ref
+                .get_plugin_descriptor
+                .context("Incomplete plugin factory")?)(factory, 0);
+            if descriptor.is_null() {
+                bail!("The plugin has no descriptor");
+            }
+
+            let host = Box::new(clap_host {
+                clap_version: CLAP_VERSION,
+                host_data: null_mut(),
+                name: c"npcli".as_ptr(),
+                vendor: c"npcli".as_ptr(),
+                url: null(),
+                version: c"0.1.0".as_ptr(),
+                get_extension: Some(host_get_extension),
+                request_restart: Some(host_request),
+                request_process: Some(host_request),
+                request_callback: Some(host_request),
+            });
+            let plugin = (factory_ref
+                .create_plugin
+                .context("Incomplete plugin factory")?)(
+                factory, &*host, (*descriptor).id
+            );
+            if plugin.is_null() {
+                bail!("The plugin couldn't be created");
+            }
+
+            let instance = Self {
+                plugin,
+                entry,
+                _host: host,
+                _library: library,
+                activated: false,
+            };
+            if !(instance.vtable().init.context("Incomplete plugin")?)(plugin) {
+                bail!("The plugin failed to initialize");
+            }
+            Ok(instance)
+        }
+    }
+
+    fn vtable(&self) -> &clap_plugin {
+        // SAFETY: the plugin stays valid until it's destroyed in drop()
+        unsafe { &*self.plugin }
+    }
+
+    unsafe fn extension<T>(&self, id: &CStr) -> Option<&T> {
+        let get_extension = self.vtable().get_extension?;
+        let extension = get_extension(self.plugin, id.as_ptr()) as *const T;
+        extension.as_ref()
+    }
+
+    /// The plugin's name, from its descriptor.
+    pub fn name(&self) -> String {
+        // SAFETY: the descriptor's strings are valid for as long as the plugin is
+        unsafe { CStr::from_ptr((*self.vtable().desc).name) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// The number of channels of the main input and output port, or `None` for ports that don't exist.
+    pub fn channel_counts(&self) -> (Option<u32>, Option<u32>) {
+        // SAFETY: the extension and the info are only used while the plugin is alive
+        unsafe {
+            let Some(ports) = self.extension::<clap_plugin_audio_ports>(CLAP_EXT_AUDIO_PORTS)
+            else {
+                return (None, None);
+            };
+            let main_port = |is_input| {
+                if (ports.count?)(self.plugin, is_input) == 0 {
+                    return None;
+                }
+                let mut info: clap_audio_port_info = std::mem::zeroed();
+                (ports.get?)(self.plugin, 0, is_input, &mut info).then_some(info.channel_count)
+            };
+            (main_port(true), main_port(false))
+        }
+    }
+
+    pub fn params(&self) -> Vec<Param> {
+        // SAFETY: see channel_counts()
+        unsafe {
+            let Some(params) = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS) else {
+                return Vec::new();
+            };
+            let (Some(count), Some(get_info)) = (params.count, params.get_info) else {
+                return Vec::new();
+            };
+            (0..count(self.plugin))
+                .filter_map(|index| {
+                    let mut info: clap_param_info = std::mem::zeroed();
+                    get_info(self.plugin, index, &mut info).then(|| Param {
+                        id: info.id,
+                        name: CStr::from_ptr(info.name.as_ptr())
+                            .to_string_lossy()
+                            .into_owned(),
+                    })
+                })
+                .collect()
+        }
+    }
+
+    /// Converts text like `-6 dB` into the parameter's plain value, using the plugin's own parser.
+    pub fn text_to_value(&self, id: u32, text: &str) -> Option<f64> {
+        let text = CString::new(text).ok()?;
+        // SAFETY: see channel_counts()
+        unsafe {
+            let params = self.extension::<clap_plugin_params>(CLAP_EXT_PARAMS)?;
+            let mut value = 0.0;
+            (params.text_to_value?)(self.plugin, id, text.as_ptr(), &mut value).then_some(value)
+        }
+    }
+
+    pub fn activate(&mut self, sample_rate: f64, max_block_size: u32) -> Result<()> {
+        // SAFETY: activate() and start_processing() are called in the order CLAP requires
+        unsafe {
+            let vtable = *self.vtable();
+            if !(vtable.activate.context("Incomplete plugin")?)(
+                self.plugin,
+                sample_rate,
+                1,
+                max_block_size,
+            ) {
+                bail!("The plugin failed to activate");
+            }
+            self.activated = true;
+            if !(vtable.start_processing.context("Incomplete plugin")?)(self.plugin) {
+                bail!("The plugin failed to start processing");
+            }
+        }
+        Ok(())
+    }
+
+    /// Processes one block. `inputs` and `outputs` hold one slice per channel, all `frames` long.
+    /// The parameter changes are applied at the start of the block.
+    pub fn process(
+        &mut self,
+        steady_time: i64,
+        frames: u32,
+        inputs: &mut [&mut [f32]],
+        outputs: &mut [&mut [f32]],
+        param_changes: &[(u32, f64)],
+    ) -> Result<()> {
+        let mut input_pointers: Vec<*mut f32> = inputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+        let mut output_pointers: Vec<*mut f32> =
+            outputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+        let input_buffer = audio_buffer(&mut input_pointers);
+        let mut output_buffer = audio_buffer(&mut output_pointers);
+
+        let events: Vec<clap_event_param_value> = param_changes
+            .iter()
+            .map(|&(param_id, value)| clap_event_param_value {
+                header: clap_event_header {
+                    size: std::mem::size_of::<clap_event_param_value>() as u32,
+                    time: 0,
+                    space_id: CLAP_CORE_EVENT_SPACE_ID,
+                    type_: CLAP_EVENT_PARAM_VALUE,
+                    flags: 0,
+                },
+                param_id,
+                cookie: null_mut(),
+                note_id: -1,
+                port_index: -1,
+                channel: -1,
+                key: -1,
+                value,
+            })
+            .collect();
+        let in_events = clap_input_events {
+            ctx: &events as *const _ as *mut c_void,
+            size: Some(input_events_size),
+            get: Some(input_events_get),
+        };
+        let out_events = clap_output_events {
+            ctx: null_mut(),
+            try_push: Some(output_events_try_push),
+        };
+
+        let process = clap_process {
+            steady_time,
+            frames_count: frames,
+            transport: null(),
+            audio_inputs: &input_buffer,
+            audio_outputs: &mut output_buffer,
+            audio_inputs_count: u32::from(!inputs.is_empty()),
+            audio_outputs_count: u32::from(!outputs.is_empty()),
+            in_events: &in_events,
+            out_events: &out_events,
+        };
+        // SAFETY: every pointer in `process` outlives this call
+        let status =
+            unsafe { (self.vtable().process.context("Incomplete plugin")?)(self.plugin, &process) };
+        if status == CLAP_PROCESS_ERROR {
+            bail!("The plugin returned an error while processing");
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Plugin {
+    fn drop(&mut self) {
+        // SAFETY: the plugin is torn down in the reverse order it was set up in
+        unsafe {
+            let vtable = *self.vtable();
+            if self.activated {
+                if let Some(stop_processing) = vtable.stop_processing {
+                    stop_processing(self.plugin);
+                }
+                if let Some(deactivate) = vtable.deactivate {
+                    deactivate(self.plugin);
+                }
+            }
+            if let Some(destroy) = vtable.destroy {
+                destroy(self.plugin);
+            }
+            if let Some(deinit) = (*self.entry).deinit {
+                deinit();
+            }
+        }
+    }
+}
+
+fn audio_buffer(channels: &mut [*mut f32]) -> clap_audio_buffer {
+    clap_audio_buffer {
+        data32: channels.as_mut_ptr(),
+        data64: null_mut(),
+        channel_count: channels.len() as u32,
+        latency: 0,
+        constant_mask: 0,
+    }
+}
+
+// the host doesn't offer any extensions, and there's nothing to restart or call back into offline
+
+unsafe extern "C" fn host_get_extension(
+    _host: *const clap_host,
+    _id: *const c_char,
+) -> *const c_void {
+    null()
+}
+
+unsafe extern "C" fn host_request(_host: *const clap_host) {}
+
+unsafe extern "C" fn input_events_size(list: *const clap_input_events) -> u32 {
+    let events = &*((*list).ctx as *const Vec<clap_event_param_value>);
+    events.len() as u32
+}
+
+unsafe extern "C" fn input_events_get(
+    list: *const clap_input_events,
+    index: u32,
+) -> *const clap_event_header {
+    let events = &*((*list).ctx as *const Vec<clap_event_param_value>);
+    events
+        .get(index as usize)
+        .map_or(null(), |event| &event.header)
+}
+
+// whatever the plugin sends back (e.g. parameter changes) isn't needed for rendering
+unsafe extern "C" fn output_events_try_push(
+    _list: *const clap_output_events,
+    _event: *const clap_event_header,
+) -> bool {
+    true
+}
@@ -0,0 +1,191 @@
+// This module runs a WAV file through a built CLAP plugin, without a DAW or sound card.
+
+mod host;
+
+use crate::bundle::select_packages;
+use crate::install::bundle_name;
+use crate::install::Format;
+use crate::validate::find_binary;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::MetadataCommand;
+use cliclack::log::{info, success};
+use host::Plugin;
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+use std::path::{Path, PathBuf};
+
+/// Renders `input` through the plugin and writes the result to `output` as 32-bit float WAV.
+///
+/// The plugin is either the given `.clap` bundle, or the bundled CLAP of `package`
+/// (which may be left out if the workspace only has one plugin crate).
+/// Parameters are set by name with `name=value`, where the value is parsed by the plugin itself,
+/// e.g. `gain=-6dB`.
+pub fn render(
+    input: PathBuf,
+    output: PathBuf,
+    params: Vec<String>,
+    plugin: Option<PathBuf>,
+    package: Option<String>,
+    block_size: u32,
+) -> Result<()> {
+    let bundle = match plugin {
+        Some(bundle) => bundle,
+        None => find_bundle(package)?,
+    };
+    if Format::of(&bundle) != Some(Format::Clap) {
+        bail!("\"{}\" is not a CLAP plugin", bundle.display());
+    }
+    let stem = bundle
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .context("The bundle's name is not valid UTF-8")?;
+    let binary = find_binary(&bundle, stem, Format::Clap).map_err(anyhow::Error::msg)?;
+
+    let (spec, mut channels) = read_wav(&input)?;
+    let frames = channels[0].len();
+
+    let mut plugin = Plugin::load(&binary)?;
+    info(format!("Loaded {}", plugin.name()))?;
+    let param_changes = parse_params(&plugin, &params)?;
+
+    // match the file's channels to the plugin's main ports, e.g. by playing mono files on both sides
+    let (input_count, output_count) = plugin.channel_counts();
+    let input_count = input_count.unwrap_or(0) as usize;
+    let output_count = output_count.unwrap_or(spec.channels as u32) as usize;
+    if input_count > channels.len() {
+        let first = channels[0].clone();
+        channels.resize(input_count, first);
+    }
+    channels.truncate(input_count);
+    let mut outputs = vec![vec![0.0f32; frames]; output_count];
+
+    plugin.activate(spec.sample_rate as f64, block_size)?;
+    let mut start = 0;
+    while start < frames {
+        let end = (start + block_size as usize).min(frames);
+        let mut input_blocks: Vec<&mut [f32]> =
+            channels.iter_mut().map(|c| &mut c[start..end]).collect();
+        let mut output_blocks: Vec<&mut [f32]> =
+            outputs.iter_mut().map(|c| &mut c[start..end]).collect();
+        // parameters only have to be set once, at the very start
+        let changes = if start == 0 { &param_changes[..] } else { &[] };
+        plugin.process(
+            start as i64,
+            (end - start) as u32,
+            &mut input_blocks,
+            &mut output_blocks,
+            changes,
+        )?;
+        start = end;
+    }
+    drop(plugin);
+
+    write_wav(&output, spec.sample_rate, &outputs)?;
+    success(format!(
+        "Rendered {} frames to \"{}\"",
+        frames,
+        output.display()
+    ))?;
+    Ok(())
+}
+
+/// The bundled CLAP plugin of `package`, or of the workspace's only plugin crate.
+fn find_bundle(package: Option<String>) -> Result<PathBuf> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let packages = select_packages(&metadata, package.into_iter().collect())?;
+    let [package] = &packages[..] else {
+        bail!(
+            "There are several plugin crates ({}). Pick one with `--package`.",
+            packages.join(", ")
+        );
+    };
+
+    let bundle = metadata
+        .target_directory
+        .as_std_path()
+        .join("bundled")
+        .join(format!("{}.clap", bundle_name(&metadata, package)));
+    if !bundle.exists() {
+        bail!(
+            "\"{}\" does not exist. Make sure the plugin exports CLAP, and run `npcli bundle` first.",
+            bundle.display()
+        );
+    }
+    Ok(bundle)
+}
+
+/// Turns `name=value` pairs into parameter IDs and plain values.
+fn parse_params(plugin: &Plugin, params: &[String]) -> Result<Vec<(u32, f64)>> {
+    let available = plugin.params();
+    params
+        .iter()
+        .map(|param| {
+            let (name, text) = param
+                .split_once('=')
+                .with_context(|| format!("Expected `name=value`, got \"{}\"", param))?;
+            let Some(found) = available
+                .iter()
+                .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+            else {
+                let names: Vec<&str> = available.iter().map(|p| p.name.as_str()).collect();
+                bail!(
+                    "The plugin has no parameter named \"{}\". Its parameters are: {}",
+                    name,
+                    names.join(", ")
+                );
+            };
+            let value = plugin
+                .text_to_value(found.id, text.trim())
+                .or_else(|| text.trim().parse().ok())
+                .with_context(|| format!("\"{}\" is not a valid value for {}", text, found.name))?;
+            Ok((found.id, value))
+        })
+        .collect()
+}
+
+/// Reads a WAV file into one buffer per channel, converting the samples to `f32`.
+fn read_wav(path: &Path) -> Result<(WavSpec, Vec<Vec<f32>>)> {
+    let mut reader =
+        WavReader::open(path).with_context(|| format!("Failed to read \"{}\"", path.display()))?;
+    let spec = reader.spec();
+    let samples: Vec<f32> = match spec.sample_format {
+        SampleFormat::Float => reader.samples::<f32>().collect::<Result<_, _>>()?,
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|sample| sample.map(|s| s as f32 / scale))
+                .collect::<Result<_, _>>()?
+        }
+    };
+    if samples.is_empty() {
+        bail!("\"{}\" doesn't contain any audio", path.display());
+    }
+
+    let channel_count = spec.channels as usize;
+    let mut channels = vec![Vec::with_capacity(samples.len() / channel_count); channel_count];
+    for frame in samples.chunks(channel_count) {
+        for (channel, &sample) in channels.iter_mut().zip(frame) {
+            channel.push(sample);
+        }
+    }
+    Ok((spec, channels))
+}
+
+fn write_wav(path: &Path, sample_rate: u32, channels: &[Vec<f32>]) -> Result<()> {
+    let spec = WavSpec {
+        channels: channels.len() as u16,
+        sample_rate,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+    let mut writer = WavWriter::create(path, spec)
+        .with_context(|| format!("Failed to create \"{}\"", path.display()))?;
+    let frames = channels.first().map_or(0, Vec::len);
+    for frame in 0..frames {
+        for channel in channels {
+            writer.write_sample(channel[frame])?;
+        }
+    }
+    writer.finalize()?;
+    Ok(())
+}
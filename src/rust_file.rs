@@ -0,0 +1,630 @@
+// This module edits Rust source files through their syntax tree, without reformatting them.
+// Only the text of whatever changes is replaced, so the user's comments and formatting survive.
+
+use anyhow::{bail, Context, Result};
+use proc_macro2::extra::DelimSpan;
+use proc_macro2::Span;
+use std::fs::{read_to_string, write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Expr, Fields, ImplItem, Item, ItemImpl, Lit, MacroDelimiter, Stmt, Type, UseTree};
+
+/// A Rust file that has been read into memory. Nothing is written until `save()` is called.
+pub struct RustFile {
+    path: PathBuf,
+    pub source: String,
+}
+
+impl RustFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let source = read_to_string(&path)
+            .with_context(|| format!("Failed to read \"{}\"", path.display()))?;
+        let file = Self { path, source };
+        file.parse()?;
+        Ok(file)
+    }
+
+    /// A file that isn't on disk, e.g. from an older git revision. `path` is only used in messages.
+    pub fn from_source(path: PathBuf, source: String) -> Result<Self> {
+        let file = Self { path, source };
+        file.parse()?;
+        Ok(file)
+    }
+
+    /// Opens every `.rs` file in `dir` (recursively) and returns the first one that `matches`.
+    pub fn find(dir: &Path, matches: impl Fn(&RustFile) -> bool) -> Result<Option<Self>> {
+        for path in rust_files(dir)? {
+            let file = Self::open(&path)?;
+            if matches(&file) {
+                return Ok(Some(file));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn save(&self) -> Result<()> {
+        write(&self.path, &self.source)?;
+        Ok(())
+    }
+
+    /// Parses the current source. The spans of the result point into `source`.
+    pub fn parse(&self) -> Result<syn::File> {
+        syn::parse_file(&self.source)
+            .with_context(|| format!("Failed to parse \"{}\"", self.path.display()))
+    }
+
+    /// The source text of a node from `parse()`, exactly as it's written.
+    pub fn text(&self, node: &impl Spanned) -> &str {
+        &self.source[range(node.span())]
+    }
+
+    /// Returns the name of the type in the first `impl <trait_name> for <type>`.
+    pub fn impl_type(&self, trait_name: &str) -> Result<Option<String>> {
+        let file = self.parse()?;
+        Ok(find_impl(&file, trait_name, None).and_then(|item| type_name(&item.self_ty)))
+    }
+
+    /// Returns the value of a string const in `impl <trait_name> for <type_name>`, e.g. a plugin's `VENDOR`.
+    /// Without a `type_name`, the first impl of the trait is used.
+    pub fn str_const(
+        &self,
+        trait_name: &str,
+        type_name: Option<&str>,
+        name: &str,
+    ) -> Result<Option<String>> {
+        let file = self.parse()?;
+        let value = find_impl(&file, trait_name, type_name)
+            .and_then(|item| find_const(item, name))
+            .and_then(|expr| match expr {
+                Expr::Lit(lit) => match &lit.lit {
+                    Lit::Str(value) => Some(value.value()),
+                    _ => None,
+                },
+                _ => None,
+            });
+        Ok(value)
+    }
+
+    /// Adds a field to a struct with named fields. `field` may span several lines, e.g. with an
+    /// `#[id = "..."]` attribute, and it's indented like the existing fields.
+    pub fn add_field(&mut self, struct_name: &str, field: &str) -> Result<()> {
+        let file = self.parse()?;
+        let item = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Struct(item) if item.ident == struct_name => Some(item),
+                _ => None,
+            })
+            .with_context(|| {
+                format!(
+                    "There's no struct {} in \"{}\"",
+                    struct_name,
+                    self.path.display()
+                )
+            })?;
+        let Fields::Named(fields) = &item.fields else {
+            bail!("{} doesn't have named fields", struct_name);
+        };
+        let (range, text) = self.push_to_list(&fields.named, &fields.brace_token.span, field);
+        self.splice(range, &text)
+    }
+
+    /// Adds a field initializer (e.g. `mix: FloatParam::new(...)`) to the struct expression that
+    /// `impl Default for <type_name>` returns.
+    pub fn add_field_init(&mut self, type_name: &str, init: &str) -> Result<()> {
+        let file = self.parse()?;
+        let item = find_impl(&file, "Default", Some(type_name)).with_context(|| {
+            format!(
+                "There's no `impl Default for {}` in \"{}\"",
+                type_name,
+                self.path.display()
+            )
+        })?;
+        let fields = item
+            .items
+            .iter()
+            .find_map(|item| match item {
+                ImplItem::Fn(function) if function.sig.ident == "default" => {
+                    match function.block.stmts.last() {
+                        Some(Stmt::Expr(Expr::Struct(expr), None)) => Some(expr),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+            .with_context(|| {
+                format!(
+                    "`{}::default()` doesn't end with a struct expression",
+                    type_name
+                )
+            })?;
+        let (range, text) = self.push_to_list(&fields.fields, &fields.brace_token.span, init);
+        self.splice(range, &text)
+    }
+
+    /// Appends an element to a top-level `const NAME: &[T] = &[...];`.
+    pub fn add_array_element(&mut self, const_name: &str, element: &str) -> Result<()> {
+        let file = self.parse()?;
+        let array = file
+            .items
+            .iter()
+            .find_map(|item| match item {
+                Item::Const(item) if item.ident == const_name => Some(&*item.expr),
+                _ => None,
+            })
+            .map(|expr| match expr {
+                Expr::Reference(reference) => &*reference.expr,
+                expr => expr,
+            })
+            .and_then(|expr| match expr {
+                Expr::Array(array) => Some(array),
+                _ => None,
+            })
+            .with_context(|| {
+                format!(
+                    "There's no array const {} in \"{}\"",
+                    const_name,
+                    self.path.display()
+                )
+            })?;
+        let (range, text) = self.push_to_list(&array.elems, &array.bracket_token.span, element);
+        self.splice(range, &text)
+    }
+
+    /// Whether a top-level `use` brings `name` into scope, e.g. `Arc` with `use std::sync::{Arc, Mutex};`.
+    pub fn uses(&self, name: &str) -> Result<bool> {
+        let file = self.parse()?;
+        Ok(file.items.iter().any(|item| match item {
+            Item::Use(item) => use_tree_has(&item.tree, name),
+            _ => false,
+        }))
+    }
+
+    /// Returns the arguments of a top-level macro call like `nih_export_clap!(Gain, Reverb)`.
+    pub fn macro_args(&self, name: &str) -> Result<Option<Vec<String>>> {
+        let file = self.parse()?;
+        Ok(find_macro_args(&file, name).map(|args| {
+            self.source[args]
+                .split(',')
+                .map(|arg| arg.trim().to_owned())
+                .filter(|arg| !arg.is_empty())
+                .collect()
+        }))
+    }
+
+    /// Replaces the arguments of a top-level macro call. The whitespace around them stays, so
+    /// arguments on their own lines stay there.
+    pub fn set_macro_args(&mut self, name: &str, args: &[String]) -> Result<()> {
+        let file = self.parse()?;
+        let mut range = find_macro_args(&file, name)
+            .with_context(|| format!("There's no `{}!` in \"{}\"", name, self.path.display()))?;
+        let current = &self.source[range.clone()];
+        if !current.trim().is_empty() {
+            range.end = range.start + current.trim_end().len();
+            range.start += current.len() - current.trim_start().len();
+        }
+        self.splice(range, &args.join(", "))
+    }
+
+    /// Inserts items (e.g. `mod reverb;`) after the `mod` and `use` items at the top of the file.
+    pub fn add_items_after_imports(&mut self, items: &str) -> Result<()> {
+        let file = self.parse()?;
+        let last_import = file
+            .items
+            .iter()
+            .take_while(|item| matches!(item, Item::Mod(_) | Item::Use(_)))
+            .last();
+        match last_import {
+            Some(item) => {
+                let end = self.after_line_comment(range(item.span()).end);
+                self.splice(end..end, &format!("\n{}", items.trim_end()))
+            }
+            // after any inner attributes and leading comments
+            None => {
+                let start = file
+                    .items
+                    .first()
+                    .map_or(self.source.len(), |item| range(item.span()).start);
+                self.splice(start..start, &format!("{}\n\n", items.trim_end()))
+            }
+        }
+    }
+
+    /// Works out the edit that appends `entry` to a comma-separated list in braces or brackets.
+    fn push_to_list<T: Spanned, P: Spanned>(
+        &self,
+        list: &Punctuated<T, P>,
+        delimiters: &DelimSpan,
+        entry: &str,
+    ) -> (Range<usize>, String) {
+        let close = range(delimiters.close()).start;
+        let Some(last) = list.pairs().last() else {
+            // an empty `{}` gets the entry on its own line, after any comments in it
+            let open = range(delimiters.open()).end;
+            let indent = self.indent_at(close).to_owned();
+            let entry = indent_lines(entry, &format!("{}    ", indent));
+            let inside = &self.source[open..close];
+            if inside.trim().is_empty() {
+                return (open..close, format!("\n{},\n{}", entry, indent));
+            }
+            let end = open + inside.trim_end().len();
+            return (end..end, format!("\n{},", entry));
+        };
+
+        let value_end = range(last.value().span()).end;
+        let indent = self.indent_at(range(last.value().span()).start).to_owned();
+        let text = format!("\n{},", indent_lines(entry, &indent));
+        // a `// comment` after the last entry belongs to it, so the new entry goes below it
+        match last.punct() {
+            Some(comma) => {
+                let end = self.after_line_comment(range(comma.span()).end);
+                (end..end, text)
+            }
+            None => {
+                let end = self.after_line_comment(value_end);
+                let comment = &self.source[value_end..end];
+                (value_end..end, format!(",{}{}", comment, text))
+            }
+        }
+    }
+
+    /// Replaces `range` with `text`. Everything else stays byte-for-byte the same, and the result
+    /// has to parse again, or nothing is changed.
+    fn splice(&mut self, range: Range<usize>, text: &str) -> Result<()> {
+        let original = self.source.clone();
+        self.source.replace_range(range, text);
+        if let Err(err) = self.parse() {
+            self.source = original;
+            return Err(err.context("The edit would have broken the file, so it wasn't made"));
+        }
+        Ok(())
+    }
+
+    /// The end of a `// comment` that follows `offset` on the same line, or `offset` without one.
+    fn after_line_comment(&self, offset: usize) -> usize {
+        let rest = &self.source[offset..];
+        let line = &rest[..rest.find('\n').unwrap_or(rest.len())];
+        if line.trim_start().starts_with("//") {
+            offset + line.trim_end().len()
+        } else {
+            offset
+        }
+    }
+
+    /// The whitespace at the start of the line that `offset` is on.
+    fn indent_at(&self, offset: usize) -> &str {
+        let line_start = self.source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = &self.source[line_start..];
+        &line[..line.len() - line.trim_start().len()]
+    }
+}
+
+fn range(span: Span) -> Range<usize> {
+    span.byte_range()
+}
+
+/// Indents every line of `text`, e.g. an attribute and the field below it. Lines keep their
+/// indentation relative to the first one, so that e.g. a builder chain stays indented.
+fn indent_lines(text: &str, indent: &str) -> String {
+    let lines: Vec<&str> = text
+        .trim_end()
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+    let leading_whitespace = |line: &str| line.chars().take_while(|c| c.is_whitespace()).count();
+    let base = lines.first().map_or(0, |line| leading_whitespace(line));
+    lines
+        .iter()
+        .map(|line| {
+            if line.trim().is_empty() {
+                return String::new();
+            }
+            let (cut, _) = line
+                .char_indices()
+                .nth(leading_whitespace(line).min(base))
+                .unwrap_or((line.len(), ' '));
+            format!("{}{}", indent, &line[cut..])
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The last segment of a path type, e.g. `GainParams` for `crate::params::GainParams`.
+pub fn type_name(ty: &Type) -> Option<String> {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map(|segment| segment.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Every `impl <trait_name> for ...` in the file.
+pub fn impls<'a>(file: &'a syn::File, trait_name: &str) -> impl Iterator<Item = &'a ItemImpl> {
+    let trait_name = trait_name.to_owned();
+    file.items.iter().filter_map(move |item| {
+        let Item::Impl(item) = item else {
+            return None;
+        };
+        let implements = item.trait_.as_ref().is_some_and(|(_, path, _)| {
+            path.segments
+                .last()
+                .is_some_and(|segment| segment.ident == trait_name)
+        });
+        implements.then_some(item)
+    })
+}
+
+/// Finds `impl <trait_name> for <ty>`, or the first impl of the trait without a `ty`.
+pub fn find_impl<'a>(
+    file: &'a syn::File,
+    trait_name: &str,
+    ty: Option<&str>,
+) -> Option<&'a ItemImpl> {
+    impls(file, trait_name)
+        .find(|item| ty.is_none_or(|ty| type_name(&item.self_ty).as_deref() == Some(ty)))
+}
+
+pub fn find_const<'a>(item: &'a ItemImpl, name: &str) -> Option<&'a Expr> {
+    item.items.iter().find_map(|item| match item {
+        ImplItem::Const(item) if item.ident == name => Some(&item.expr),
+        _ => None,
+    })
+}
+
+/// The byte range between the parentheses of a top-level `name!(...)`.
+fn find_macro_args(file: &syn::File, name: &str) -> Option<Range<usize>> {
+    file.items.iter().find_map(|item| match item {
+        Item::Macro(item) if item.mac.path.is_ident(name) => match &item.mac.delimiter {
+            MacroDelimiter::Paren(paren) => {
+                Some(range(paren.span.open()).end..range(paren.span.close()).start)
+            }
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn use_tree_has(tree: &UseTree, name: &str) -> bool {
+    match tree {
+        UseTree::Path(path) => use_tree_has(&path.tree, name),
+        UseTree::Name(use_name) => use_name.ident == name,
+        UseTree::Rename(rename) => rename.rename == name,
+        UseTree::Group(group) => group.items.iter().any(|tree| use_tree_has(tree, name)),
+        // a glob might, but there's no way to tell without the crate it imports from
+        UseTree::Glob(_) => false,
+    }
+}
+
+/// Whether `attrs` has a `#[derive(...)]` that includes `name`.
+pub fn derives(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("derive")
+            && attr
+                .parse_args_with(Punctuated::<syn::Path, syn::Token![,]>::parse_terminated)
+                .is_ok_and(|paths| {
+                    paths.iter().any(|path| {
+                        path.segments
+                            .last()
+                            .is_some_and(|segment| segment.ident == name)
+                    })
+                })
+    })
+}
+
+/// Every `.rs` file in `dir` and its subdirectories, sorted.
+pub fn rust_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in dir.read_dir()? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(rust_files(&path)?);
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs `edit` on `source`, and checks that the result still parses and is exactly `expected`,
+    /// which is `source` with only the edit's bytes changed.
+    fn assert_edit(source: &str, edit: impl FnOnce(&mut RustFile) -> Result<()>, expected: &str) {
+        let mut file = RustFile::from_source(PathBuf::from("lib.rs"), source.to_owned()).unwrap();
+        edit(&mut file).unwrap();
+        file.parse().unwrap();
+        assert_eq!(file.source, expected);
+    }
+
+    #[test]
+    fn adds_fields_after_the_last_one_and_its_comment() {
+        let source = r#"// the params
+#[derive(Params)]
+pub struct GainParams {
+    /// The gain, in dB
+    #[id = "gain"]
+    pub gain:   FloatParam, // odd spacing, on purpose
+
+    // more params go here
+}
+
+struct Empty {
+    // nothing yet
+}
+"#;
+        assert_edit(
+            source,
+            |file| file.add_field("GainParams", "#[id = \"mix\"]\npub mix: FloatParam"),
+            r#"// the params
+#[derive(Params)]
+pub struct GainParams {
+    /// The gain, in dB
+    #[id = "gain"]
+    pub gain:   FloatParam, // odd spacing, on purpose
+    #[id = "mix"]
+    pub mix: FloatParam,
+
+    // more params go here
+}
+
+struct Empty {
+    // nothing yet
+}
+"#,
+        );
+        assert_edit(
+            source,
+            |file| file.add_field("Empty", "count: u32"),
+            r#"// the params
+#[derive(Params)]
+pub struct GainParams {
+    /// The gain, in dB
+    #[id = "gain"]
+    pub gain:   FloatParam, // odd spacing, on purpose
+
+    // more params go here
+}
+
+struct Empty {
+    // nothing yet
+    count: u32,
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn adds_field_inits_with_their_relative_indentation() {
+        let source = r#"impl Default for GainParams {
+    fn default() -> Self {
+        Self {
+            gain: FloatParam::new("Gain", 0.0, FloatRange::Linear { min: -30.0, max: 30.0 })
+                .with_unit(" dB") // no trailing comma
+        }
+    }
+}
+"#;
+        assert_edit(
+            source,
+            |file| {
+                file.add_field_init(
+                    "GainParams",
+                    "mix: FloatParam::new(\"Mix\", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })\n    .with_unit(\"%\")\n    .with_smoother(SmoothingStyle::Linear(50.0))",
+                )
+            },
+            r#"impl Default for GainParams {
+    fn default() -> Self {
+        Self {
+            gain: FloatParam::new("Gain", 0.0, FloatRange::Linear { min: -30.0, max: 30.0 })
+                .with_unit(" dB"), // no trailing comma
+            mix: FloatParam::new("Mix", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_smoother(SmoothingStyle::Linear(50.0)),
+        }
+    }
+}
+"#,
+        );
+    }
+
+    #[test]
+    fn adds_array_elements() {
+        let source = r#"/// Loaded by `PresetMenu`
+pub const FACTORY_PRESETS: &[&str] = &[
+        include_str!("../presets/init.json"),   // the defaults
+];
+"#;
+        assert_edit(
+            source,
+            |file| {
+                file.add_array_element("FACTORY_PRESETS", "include_str!(\"../presets/loud.json\")")
+            },
+            r#"/// Loaded by `PresetMenu`
+pub const FACTORY_PRESETS: &[&str] = &[
+        include_str!("../presets/init.json"),   // the defaults
+        include_str!("../presets/loud.json"),
+];
+"#,
+        );
+    }
+
+    #[test]
+    fn sets_macro_args_in_place() {
+        let source = r#"// both formats
+nih_export_clap!(Gain);
+nih_export_vst3!(
+    Gain
+); // VST3 too
+"#;
+        assert_edit(
+            source,
+            |file| {
+                file.set_macro_args("nih_export_vst3", &["Gain".to_owned(), "Reverb".to_owned()])
+            },
+            r#"// both formats
+nih_export_clap!(Gain);
+nih_export_vst3!(
+    Gain, Reverb
+); // VST3 too
+"#,
+        );
+    }
+
+    #[test]
+    fn adds_items_after_imports() {
+        assert_edit(
+            r#"//! The gain plugin
+#![allow(clippy::needless_range_loop)]
+
+mod dsp;
+use nih_plug::prelude::*; // everything
+
+/// The plugin
+struct Gain;
+"#,
+            |file| file.add_items_after_imports("mod reverb;\n"),
+            r#"//! The gain plugin
+#![allow(clippy::needless_range_loop)]
+
+mod dsp;
+use nih_plug::prelude::*; // everything
+mod reverb;
+
+/// The plugin
+struct Gain;
+"#,
+        );
+        assert_edit(
+            r#"#![allow(unused)]
+// no imports yet
+
+struct Gain;
+"#,
+            |file| file.add_items_after_imports("use std::sync::Arc;"),
+            r#"#![allow(unused)]
+// no imports yet
+
+use std::sync::Arc;
+
+struct Gain;
+"#,
+        );
+    }
+}
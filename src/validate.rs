@@ -0,0 +1,358 @@
+// This module inspects bundled plugins without loading them in a host.
+
+use crate::doctor::{summarize, Check, Status};
+use crate::install::{bundle_name, Format};
+use crate::npcli_toml::{NpcliToml, FILENAME};
+use anyhow::{bail, Context, Result};
+use cargo_metadata::{Metadata, MetadataCommand};
+use cliclack::log::step;
+use goblin::mach::{Mach, MachO, SingleArch};
+use goblin::Object;
+use std::fs::{read, read_to_string};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Checks each bundle's structure, exported entry points, and IDs, and prints the results.
+/// With `external` set, clap-validator (for CLAP) or pluginval (for VST3) also run, if installed.
+pub fn validate(bundles: Vec<PathBuf>, external: bool) -> Result<()> {
+    // outside of a project there's no npcli.toml, but everything else can still be checked
+    let metadata = MetadataCommand::new().no_deps().exec().ok();
+
+    let mut all_checks = Vec::new();
+    for bundle in &bundles {
+        step(format!("Validating {}", bundle.display()))?;
+        let checks = validate_bundle(bundle, metadata.as_ref(), external)?;
+        for check in &checks {
+            check.print()?;
+        }
+        all_checks.extend(checks);
+    }
+    summarize(&all_checks)
+}
+
+fn validate_bundle(
+    bundle: &Path,
+    metadata: Option<&Metadata>,
+    external: bool,
+) -> Result<Vec<Check>> {
+    let Some(format) = Format::of(bundle) else {
+        bail!(
+            "\"{}\" is not a `.clap` or `.vst3` bundle",
+            bundle.display()
+        );
+    };
+    if !bundle.exists() {
+        bail!("\"{}\" does not exist", bundle.display());
+    }
+    let stem = bundle
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .context("The bundle's name is not valid UTF-8")?;
+
+    let mut checks = Vec::new();
+    let binary = match find_binary(bundle, stem, format) {
+        Ok(binary) => {
+            let relative = binary.strip_prefix(bundle).unwrap_or(&binary);
+            checks.push(Check::new(
+                "structure",
+                Status::Pass,
+                format!("binary at \"{}\"", relative.display()),
+            ));
+            Some(read(&binary)?)
+        }
+        Err(problem) => {
+            checks.push(Check::new("structure", Status::Fail, problem));
+            None
+        }
+    };
+
+    let ids = metadata.and_then(|metadata| find_ids(metadata, stem));
+    if format == Format::Vst3 {
+        checks.push(check_moduleinfo(bundle, ids.as_ref()));
+    }
+    if let Some(binary) = &binary {
+        checks.push(check_exports(binary, format));
+        match &ids {
+            Some(ids) => checks.extend(check_ids(binary, format, ids)),
+            None => checks.push(Check::new(
+                "IDs",
+                Status::Warn,
+                format!(
+                    "no {} was found for this bundle, so its IDs weren't checked",
+                    FILENAME
+                ),
+            )),
+        }
+    }
+
+    if external {
+        checks.push(run_external(bundle, format));
+    }
+    Ok(checks)
+}
+
+/// Finds the plugin's binary in the bundle, or describes what's wrong with the bundle's layout.
+pub fn find_binary(bundle: &Path, stem: &str, format: Format) -> Result<PathBuf, String> {
+    // CLAP plugins are a single file everywhere but macOS
+    if bundle.is_file() {
+        return match format {
+            Format::Clap => Ok(bundle.to_path_buf()),
+            Format::Vst3 => {
+                Err("VST3 plugins must be a bundle directory, not a single file".into())
+            }
+        };
+    }
+
+    let contents = bundle.join("Contents");
+    if !contents.is_dir() {
+        return Err("`Contents` is missing".into());
+    }
+    // macOS: `Contents/MacOS/<name>` next to `Contents/Info.plist`
+    let macos = contents.join("MacOS").join(stem);
+    if macos.is_file() {
+        if !contents.join("Info.plist").is_file() {
+            return Err("`Contents/Info.plist` is missing".into());
+        }
+        return Ok(macos);
+    }
+    // VST3 on Linux and Windows: `Contents/<arch>-linux/<name>.so` or `Contents/<arch>-win/<name>.vst3`
+    if format == Format::Vst3 {
+        let arch_dirs = contents.read_dir().map_err(|err| err.to_string())?;
+        for entry in arch_dirs.flatten() {
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            let binary = if dir_name.ends_with("-linux") {
+                entry.path().join(format!("{}.so", stem))
+            } else if dir_name.ends_with("-win") {
+                entry.path().join(format!("{}.vst3", stem))
+            } else {
+                continue;
+            };
+            if binary.is_file() {
+                return Ok(binary);
+            }
+        }
+    }
+    Err(format!(
+        "no binary named \"{}\" was found in `Contents`",
+        stem
+    ))
+}
+
+/// Reads the npcli.toml of the workspace package that produces this bundle.
+fn find_ids(metadata: &Metadata, stem: &str) -> Option<NpcliToml> {
+    let package = metadata
+        .workspace_packages()
+        .into_iter()
+        .find(|package| bundle_name(metadata, &package.name) == stem)?;
+    let dir = package.manifest_path.parent()?.as_std_path();
+    NpcliToml::load(dir).ok()?
+}
+
+/// Checks that the shared library exports the entry point hosts look for.
+fn check_exports(binary: &[u8], format: Format) -> Check {
+    let (symbol, macro_name) = match format {
+        Format::Clap => ("clap_entry", "nih_export_clap!"),
+        Format::Vst3 => ("GetPluginFactory", "nih_export_vst3!"),
+    };
+    match exported_symbols(binary) {
+        Ok(symbols) if symbols.iter().any(|name| name == symbol) => {
+            Check::new("exports", Status::Pass, format!("`{}` is exported", symbol))
+        }
+        Ok(_) => Check::new(
+            "exports",
+            Status::Fail,
+            format!("`{}` is not exported", symbol),
+        )
+        .hint(format!(
+            "Make sure lib.rs calls `{}` and that `crate-type` includes \"cdylib\"",
+            macro_name
+        )),
+        Err(err) => Check::new(
+            "exports",
+            Status::Fail,
+            format!("the binary couldn't be read: {}", err),
+        ),
+    }
+}
+
+/// The names of the symbols a shared library exports, for ELF, PE, and (fat) Mach-O binaries.
+fn exported_symbols(binary: &[u8]) -> Result<Vec<String>> {
+    let symbols = match Object::parse(binary)? {
+        Object::Elf(elf) => elf
+            .dynsyms
+            .iter()
+            .filter(|symbol| !symbol.is_import())
+            .filter_map(|symbol| elf.dynstrtab.get_at(symbol.st_name))
+            .map(str::to_owned)
+            .collect(),
+        Object::PE(pe) => pe
+            .exports
+            .iter()
+            .filter_map(|export| export.name)
+            .map(str::to_owned)
+            .collect(),
+        Object::Mach(Mach::Binary(macho)) => mach_exports(&macho)?,
+        Object::Mach(Mach::Fat(fat)) => {
+            let mut symbols = Vec::new();
+            for arch in &fat {
+                if let SingleArch::MachO(macho) = arch? {
+                    symbols.extend(mach_exports(&macho)?);
+                }
+            }
+            symbols
+        }
+        _ => bail!("unsupported binary format"),
+    };
+    Ok(symbols)
+}
+
+fn mach_exports(macho: &MachO) -> Result<Vec<String>> {
+    // Mach-O symbols have a leading underscore
+    Ok(macho
+        .exports()?
+        .into_iter()
+        .map(|export| {
+            export
+                .name
+                .strip_prefix('_')
+                .unwrap_or(&export.name)
+                .to_owned()
+        })
+        .collect())
+}
+
+/// Checks that the IDs of every plugin in npcli.toml ended up in the binary.
+fn check_ids(binary: &[u8], format: Format, ids: &NpcliToml) -> Vec<Check> {
+    let mut checks = Vec::new();
+    for (plugin, info) in &ids.plugins {
+        match format {
+            Format::Clap => {
+                if let Some(id) = &info.clap_id {
+                    checks.push(if contains(binary, id.as_bytes()) {
+                        Check::new("CLAP ID", Status::Pass, format!("{}: \"{}\"", plugin, id))
+                    } else {
+                        Check::new(
+                            "CLAP ID",
+                            Status::Fail,
+                            format!(
+                                "{}: \"{}\" from {} isn't in the binary",
+                                plugin, id, FILENAME
+                            ),
+                        )
+                        .hint(format!(
+                            "Make `CLAP_ID` of {} and {} match",
+                            plugin, FILENAME
+                        ))
+                    });
+                }
+            }
+            Format::Vst3 => {
+                if let Some(id) = &info.vst3_id {
+                    // the compiler may turn a 16 byte array into plain instructions,
+                    // so not finding it isn't necessarily a problem
+                    checks.push(if contains(binary, id.as_bytes()) {
+                        Check::new(
+                            "VST3 class ID",
+                            Status::Pass,
+                            format!("{}: \"{}\"", plugin, id),
+                        )
+                    } else {
+                        Check::new(
+                            "VST3 class ID",
+                            Status::Warn,
+                            format!(
+                                "{}: \"{}\" from {} wasn't found in the binary",
+                                plugin, id, FILENAME
+                            ),
+                        )
+                        .hint(format!(
+                            "Make sure `VST3_CLASS_ID` of {} and {} match",
+                            plugin, FILENAME
+                        ))
+                    });
+                }
+            }
+        }
+    }
+    checks
+}
+
+/// Checks `Contents/Resources/moduleinfo.json`, which is optional, but has to be right if it's there.
+fn check_moduleinfo(bundle: &Path, ids: Option<&NpcliToml>) -> Check {
+    let path = bundle
+        .join("Contents")
+        .join("Resources")
+        .join("moduleinfo.json");
+    let Ok(contents) = read_to_string(&path) else {
+        return Check::new("moduleinfo", Status::Pass, "none (it's optional)");
+    };
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(&contents) else {
+        return Check::new(
+            "moduleinfo",
+            Status::Warn,
+            "moduleinfo.json couldn't be parsed",
+        );
+    };
+    let class_ids: Vec<&str> = json
+        .get("Classes")
+        .and_then(|classes| classes.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|class| class.get("CID")?.as_str())
+        .collect();
+    if class_ids.is_empty() {
+        return Check::new("moduleinfo", Status::Fail, "no classes are listed");
+    }
+
+    let vst3_ids = ids
+        .into_iter()
+        .flat_map(|ids| ids.plugins.values())
+        .filter_map(|info| info.vst3_id.as_ref());
+    for id in vst3_ids {
+        let expected: String = id.bytes().map(|byte| format!("{:02X}", byte)).collect();
+        if !class_ids
+            .iter()
+            .any(|cid| cid.eq_ignore_ascii_case(&expected))
+        {
+            return Check::new(
+                "moduleinfo",
+                Status::Fail,
+                format!("no class has the ID \"{}\" ({})", id, expected),
+            );
+        }
+    }
+    Check::new(
+        "moduleinfo",
+        Status::Pass,
+        format!("{} class(es)", class_ids.len()),
+    )
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    !needle.is_empty()
+        && haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+}
+
+/// Runs clap-validator or pluginval on the bundle, if it's installed. Their output is shown as-is.
+fn run_external(bundle: &Path, format: Format) -> Check {
+    let (tool, args, url) = match format {
+        Format::Clap => (
+            "clap-validator",
+            &["validate"][..],
+            "https://github.com/free-audio/clap-validator",
+        ),
+        Format::Vst3 => (
+            "pluginval",
+            &["--skip-gui-tests", "--validate"][..],
+            "https://github.com/Tracktion/pluginval",
+        ),
+    };
+    match Command::new(tool).args(args).arg(bundle).status() {
+        Ok(status) if status.success() => Check::new(tool, Status::Pass, "passed"),
+        Ok(_) => Check::new(tool, Status::Fail, "found problems (see its output above)"),
+        Err(_) => Check::new(tool, Status::Warn, "not installed, so it was skipped")
+            .hint(format!("Get it from {}", url)),
+    }
+}
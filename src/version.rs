@@ -0,0 +1,258 @@
+// This module bumps plugin crate versions and records the release in the changelog and git.
+
+use crate::bundle::select_packages;
+use crate::cargo_toml::CargoToml;
+use crate::cli::BumpLevel;
+use crate::git::git;
+use anyhow::{bail, Context, Result};
+use cargo_metadata::semver::Version;
+use cargo_metadata::MetadataCommand;
+use cliclack::log::{success, warning};
+use std::fs::{read_to_string, write};
+use std::path::Path;
+use std::process::Command;
+use toml_edit::Item;
+
+const CHANGELOG_FILENAME: &str = "CHANGELOG.md";
+
+/// Bumps the version of the given packages (or every plugin crate), adds a section for it to
+/// each package's changelog, then commits and tags the release.
+/// `Plugin::VERSION` comes from `CARGO_PKG_VERSION`, so Cargo.toml is all that has to change.
+pub fn bump(level: BumpLevel, packages: Vec<String>) -> Result<()> {
+    let metadata = MetadataCommand::new().no_deps().exec()?;
+    let root = metadata.workspace_root.as_std_path();
+    if !git(root, &["status", "--porcelain"])?.trim().is_empty() {
+        bail!("The working tree has uncommitted changes. Commit or stash them before bumping the version.");
+    }
+
+    // with more than one crate in the workspace, tags need to say which one was released
+    let tag_packages = metadata.workspace_members.len() > 1;
+    // every package is checked (and its Cargo.toml bumped in memory) before anything is written,
+    // so that one that can't be bumped doesn't leave the others half-done
+    let mut bumps = Vec::new();
+    for name in select_packages(&metadata, packages)? {
+        let package = metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == name)
+            .with_context(|| format!("There's no package named {} in the workspace", name))?;
+        let dir = package
+            .manifest_path
+            .parent()
+            .context("The package has no directory")?
+            .as_std_path();
+        let version = bumped(&package.version, level);
+
+        let mut manifest = CargoToml::open(dir)?;
+        set_version(&mut manifest, &name, &version)?;
+        let previous_tag = tag_name(&name, &package.version, tag_packages);
+        let notes = release_notes(dir, &previous_tag)?;
+        bumps.push((name, &package.version, version, manifest, dir, notes));
+    }
+
+    let mut releases = Vec::new();
+    let mut changelogs = Vec::new();
+    for (name, previous, version, manifest, dir, notes) in bumps {
+        manifest.save()?;
+        let changelog = dir.join(CHANGELOG_FILENAME);
+        add_changelog_section(&changelog, &version, &notes)?;
+        changelogs.push(changelog.to_string_lossy().into_owned());
+
+        success(format!("{} {} -> {}", name, previous, version))?;
+        releases.push((name, version));
+    }
+
+    // keep Cargo.lock in sync, if there is one
+    if root.join("Cargo.lock").exists() {
+        let status = Command::new("cargo")
+            .current_dir(root)
+            .args(["update", "--workspace", "--quiet"])
+            .status()
+            .context("Failed to run cargo")?;
+        if !status.success() {
+            warning("Failed to update Cargo.lock. Run `cargo update --workspace` yourself.")?;
+        }
+    }
+
+    let summary: Vec<String> = releases
+        .iter()
+        .map(|(name, version)| format!("{} {}", name, version))
+        .collect();
+    git(root, &["add", "--update"])?;
+    let mut add = vec!["add", "--"];
+    add.extend(changelogs.iter().map(String::as_str));
+    git(root, &add)?;
+    git(
+        root,
+        &[
+            "commit",
+            "--message",
+            &format!("Release {}", summary.join(", ")),
+        ],
+    )?;
+    for (name, version) in &releases {
+        let tag = tag_name(name, version, tag_packages);
+        git(
+            root,
+            &[
+                "tag",
+                "--annotate",
+                &tag,
+                "--message",
+                &format!("{} {}", name, version),
+            ],
+        )?;
+        println!("Tagged {}...", tag);
+    }
+    println!("Push the release with `git push --follow-tags`.");
+    Ok(())
+}
+
+/// Returns the next version. A pre-release of a version that's already at the bumped level is
+/// released instead, e.g. 1.2.0-beta.1 -> 1.2.0 for a minor or patch bump, but 2.0.0 for a major one.
+fn bumped(version: &Version, level: BumpLevel) -> Version {
+    let Version {
+        major,
+        minor,
+        patch,
+        ..
+    } = *version;
+    let pre = !version.pre.is_empty();
+    match level {
+        BumpLevel::Major if pre && minor == 0 && patch == 0 => Version::new(major, 0, 0),
+        BumpLevel::Major => Version::new(major + 1, 0, 0),
+        BumpLevel::Minor if pre && patch == 0 => Version::new(major, minor, 0),
+        BumpLevel::Minor => Version::new(major, minor + 1, 0),
+        BumpLevel::Patch if pre => Version::new(major, minor, patch),
+        BumpLevel::Patch => Version::new(major, minor, patch + 1),
+    }
+}
+
+fn tag_name(package: &str, version: &Version, with_package: bool) -> String {
+    if with_package {
+        format!("{}-v{}", package, version)
+    } else {
+        format!("v{}", version)
+    }
+}
+
+/// Sets the package's version in `manifest`, without saving it.
+fn set_version(manifest: &mut CargoToml, name: &str, version: &Version) -> Result<()> {
+    let current = manifest
+        .table_mut("package")?
+        .get_mut("version")
+        .and_then(Item::as_value_mut)
+        .with_context(|| format!("{} has no version in its Cargo.toml", name))?;
+    // bumping `workspace.package.version` would bump every other crate that inherits it too
+    if current.as_str().is_none() {
+        bail!(
+            "{} inherits its version from the workspace, so it can't be bumped on its own. Give it its own `version` in Cargo.toml first.",
+            name
+        );
+    }
+    let decor = current.decor().clone();
+    *current = version.to_string().into();
+    *current.decor_mut() = decor;
+    Ok(())
+}
+
+/// Lists the commits that touched `dir` since `previous_tag`, or all of them if there's no such tag.
+fn release_notes(dir: &Path, previous_tag: &str) -> Result<String> {
+    let tag_ref = format!("refs/tags/{}", previous_tag);
+    let range = if git(dir, &["rev-parse", "--quiet", "--verify", &tag_ref]).is_ok() {
+        format!("{}..HEAD", previous_tag)
+    } else {
+        "HEAD".to_string()
+    };
+    let notes = git(dir, &["log", "--format=- %s", &range, "--", "."])?;
+    Ok(if notes.trim().is_empty() {
+        "- No changes.\n".to_string()
+    } else {
+        notes
+    })
+}
+
+/// Adds a section above the newest release. If there's an "Unreleased" section, its notes become
+/// the new release's notes instead of the commit log.
+fn add_changelog_section(path: &Path, version: &Version, notes: &str) -> Result<()> {
+    let date = chrono::Local::now().format("%Y-%m-%d");
+    let heading = format!("## [{}] - {}\n", version, date);
+    let existing = read_to_string(path).unwrap_or_default();
+
+    let mut lines: Vec<&str> = existing.split_inclusive('\n').collect();
+    let new_section = format!("{}\n{}\n", heading, notes);
+    match lines.iter().position(|line| line.starts_with("## ")) {
+        Some(i) if lines[i].to_lowercase().contains("unreleased") => {
+            lines.insert(i + 1, "\n");
+            lines.insert(i + 2, &heading);
+        }
+        Some(i) => lines.insert(i, &new_section),
+        None if existing.trim().is_empty() => {
+            lines = vec!["# Changelog\n\n", &new_section];
+        }
+        None => {
+            if !existing.ends_with('\n') {
+                lines.push("\n");
+            }
+            lines.push("\n");
+            lines.push(&new_section);
+        }
+    }
+    write(path, lines.concat())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn bump(version: &str, level: BumpLevel) -> String {
+        bumped(&Version::parse(version).unwrap(), level).to_string()
+    }
+
+    #[test]
+    fn bumps_releases() {
+        assert_eq!(bump("1.2.3", BumpLevel::Patch), "1.2.4");
+        assert_eq!(bump("1.2.3", BumpLevel::Minor), "1.3.0");
+        assert_eq!(bump("1.2.3", BumpLevel::Major), "2.0.0");
+    }
+
+    #[test]
+    fn releases_pre_releases_at_their_level() {
+        assert_eq!(bump("1.2.0-beta.1", BumpLevel::Patch), "1.2.0");
+        assert_eq!(bump("1.2.0-beta.1", BumpLevel::Minor), "1.2.0");
+        assert_eq!(bump("1.2.0-beta.1", BumpLevel::Major), "2.0.0");
+        assert_eq!(bump("1.2.3-rc.1", BumpLevel::Minor), "1.3.0");
+        assert_eq!(bump("2.0.0-alpha", BumpLevel::Major), "2.0.0");
+    }
+
+    #[test]
+    fn sets_versions_without_writing() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("Cargo.toml");
+        let contents = "[package]\nname = \"gain\"\nversion = \"0.1.0\" # bumped by npcli\n";
+        write(&path, contents).unwrap();
+
+        let mut manifest = CargoToml::open(dir.path()).unwrap();
+        set_version(&mut manifest, "gain", &Version::new(0, 2, 0)).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), contents);
+        manifest.save().unwrap();
+        assert_eq!(
+            read_to_string(&path).unwrap(),
+            contents.replace("0.1.0", "0.2.0")
+        );
+    }
+
+    #[test]
+    fn rejects_inherited_versions() {
+        let dir = tempdir().unwrap();
+        write(
+            dir.path().join("Cargo.toml"),
+            "[package]\nname = \"gain\"\nversion.workspace = true\n",
+        )
+        .unwrap();
+        let mut manifest = CargoToml::open(dir.path()).unwrap();
+        assert!(set_version(&mut manifest, "gain", &Version::new(0, 2, 0)).is_err());
+    }
+}